@@ -0,0 +1,233 @@
+//! Contexts whose size is determined at runtime.
+
+use super::{
+    Device, Device32Byte, Device64Byte, DeviceHandler, EndpointHandler, Input, Input32Byte,
+    Input64Byte, InputControlHandler, InputHandler, SlotHandler,
+};
+use crate::registers::capability::CapabilityParameters1;
+use core::mem::{size_of, size_of_val};
+use core::slice;
+
+/// Input Context whose size is determined at runtime.
+///
+/// The size of the Contexts depends on the Context Size bit of the Capability Parameters 1
+/// register. This enum makes it possible to handle both sizes with a single type. Refer to
+/// [`InputHandler`] for the available methods.
+///
+/// The layout of this enum is not the same as that of the Context. Use [`AsRef<[u32]>`] to get
+/// the dwords of the Context.
+#[allow(clippy::large_enum_variant)]
+#[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum AnyInput {
+    /// 32 byte Input Context.
+    Byte32(Input32Byte),
+    /// 64 byte Input Context.
+    Byte64(Input64Byte),
+}
+impl AnyInput {
+    /// Creates an empty Input Context of the size the xHC uses.
+    #[must_use]
+    pub fn new(hccparams1: CapabilityParameters1) -> Self {
+        if hccparams1.context_size() {
+            Self::Byte64(Input64Byte::new_64byte())
+        } else {
+            Self::Byte32(Input32Byte::new_32byte())
+        }
+    }
+
+    /// Returns the size of the Context in bytes.
+    #[must_use]
+    pub fn bytes(&self) -> usize {
+        match self {
+            Self::Byte32(i) => size_of_val(i),
+            Self::Byte64(i) => size_of_val(i),
+        }
+    }
+}
+impl InputHandler for AnyInput {
+    fn control(&self) -> &dyn InputControlHandler {
+        match self {
+            Self::Byte32(i) => i.control(),
+            Self::Byte64(i) => i.control(),
+        }
+    }
+
+    fn control_mut(&mut self) -> &mut dyn InputControlHandler {
+        match self {
+            Self::Byte32(i) => i.control_mut(),
+            Self::Byte64(i) => i.control_mut(),
+        }
+    }
+
+    fn device(&self) -> &dyn DeviceHandler {
+        match self {
+            Self::Byte32(i) => i.device(),
+            Self::Byte64(i) => i.device(),
+        }
+    }
+
+    fn device_mut(&mut self) -> &mut dyn DeviceHandler {
+        match self {
+            Self::Byte32(i) => i.device_mut(),
+            Self::Byte64(i) => i.device_mut(),
+        }
+    }
+}
+impl AsRef<[u32]> for AnyInput {
+    fn as_ref(&self) -> &[u32] {
+        match self {
+            Self::Byte32(i) => dwords(i),
+            Self::Byte64(i) => dwords(i),
+        }
+    }
+}
+impl AsMut<[u32]> for AnyInput {
+    fn as_mut(&mut self) -> &mut [u32] {
+        match self {
+            Self::Byte32(i) => dwords_mut(i),
+            Self::Byte64(i) => dwords_mut(i),
+        }
+    }
+}
+impl From<Input32Byte> for AnyInput {
+    fn from(i: Input32Byte) -> Self {
+        Self::Byte32(i)
+    }
+}
+impl From<Input64Byte> for AnyInput {
+    fn from(i: Input64Byte) -> Self {
+        Self::Byte64(i)
+    }
+}
+
+/// Device Context whose size is determined at runtime.
+///
+/// The size of the Contexts depends on the Context Size bit of the Capability Parameters 1
+/// register. This enum makes it possible to handle both sizes with a single type. Refer to
+/// [`DeviceHandler`] for the available methods.
+///
+/// The layout of this enum is not the same as that of the Context. Use [`AsRef<[u32]>`] to get
+/// the dwords of the Context.
+#[allow(clippy::large_enum_variant)]
+#[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum AnyDevice {
+    /// 32 byte Device Context.
+    Byte32(Device32Byte),
+    /// 64 byte Device Context.
+    Byte64(Device64Byte),
+}
+impl AnyDevice {
+    /// Creates an empty Device Context of the size the xHC uses.
+    #[must_use]
+    pub fn new(hccparams1: CapabilityParameters1) -> Self {
+        if hccparams1.context_size() {
+            Self::Byte64(Device64Byte::new_64byte())
+        } else {
+            Self::Byte32(Device32Byte::new_32byte())
+        }
+    }
+
+    /// Returns the size of the Context in bytes.
+    #[must_use]
+    pub fn bytes(&self) -> usize {
+        match self {
+            Self::Byte32(d) => size_of_val(d),
+            Self::Byte64(d) => size_of_val(d),
+        }
+    }
+}
+impl DeviceHandler for AnyDevice {
+    fn slot(&self) -> &dyn SlotHandler {
+        match self {
+            Self::Byte32(d) => d.slot(),
+            Self::Byte64(d) => d.slot(),
+        }
+    }
+
+    fn slot_mut(&mut self) -> &mut dyn SlotHandler {
+        match self {
+            Self::Byte32(d) => d.slot_mut(),
+            Self::Byte64(d) => d.slot_mut(),
+        }
+    }
+
+    fn endpoint(&self, dci: usize) -> &dyn EndpointHandler {
+        match self {
+            Self::Byte32(d) => d.endpoint(dci),
+            Self::Byte64(d) => d.endpoint(dci),
+        }
+    }
+
+    fn endpoint_mut(&mut self, dci: usize) -> &mut dyn EndpointHandler {
+        match self {
+            Self::Byte32(d) => d.endpoint_mut(dci),
+            Self::Byte64(d) => d.endpoint_mut(dci),
+        }
+    }
+}
+impl AsRef<[u32]> for AnyDevice {
+    fn as_ref(&self) -> &[u32] {
+        match self {
+            Self::Byte32(d) => dwords(d),
+            Self::Byte64(d) => dwords(d),
+        }
+    }
+}
+impl AsMut<[u32]> for AnyDevice {
+    fn as_mut(&mut self) -> &mut [u32] {
+        match self {
+            Self::Byte32(d) => dwords_mut(d),
+            Self::Byte64(d) => dwords_mut(d),
+        }
+    }
+}
+impl From<Device32Byte> for AnyDevice {
+    fn from(d: Device32Byte) -> Self {
+        Self::Byte32(d)
+    }
+}
+impl From<Device64Byte> for AnyDevice {
+    fn from(d: Device64Byte) -> Self {
+        Self::Byte64(d)
+    }
+}
+
+/// A marker of the Contexts which consist only of dwords.
+trait Dwords {}
+impl<const N: usize> Dwords for Input<N> {}
+impl<const N: usize> Dwords for Device<N> {}
+
+fn dwords<T: Dwords>(x: &T) -> &[u32] {
+    let p: *const T = x;
+
+    // SAFETY: `T` is a `repr(C)` struct which consists only of `u32` arrays.
+    unsafe { slice::from_raw_parts(p.cast(), size_of::<T>() / 4) }
+}
+
+fn dwords_mut<T: Dwords>(x: &mut T) -> &mut [u32] {
+    let p: *mut T = x;
+
+    // SAFETY: `T` is a `repr(C)` struct which consists only of `u32` arrays.
+    unsafe { slice::from_raw_parts_mut(p.cast(), size_of::<T>() / 4) }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn both_sizes_share_a_code_path() {
+        for mut input in [
+            AnyInput::from(Input32Byte::new_32byte()),
+            AnyInput::from(Input64Byte::new_64byte()),
+        ] {
+            input.control_mut().set_add_context_flag(1);
+            input.device_mut().endpoint_mut(1).set_max_packet_size(64);
+
+            let dwords_per_context = input.bytes() / 33 / 4;
+            assert_eq!(input.as_ref().len(), dwords_per_context * 33);
+            assert_eq!(input.as_ref()[1], 0b10);
+            assert_eq!(input.as_ref()[dwords_per_context * 2 + 1] >> 16, 64);
+        }
+    }
+}
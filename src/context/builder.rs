@@ -0,0 +1,754 @@
+//! Builders of Contexts.
+
+use super::{EndpointHandler, EndpointType, SlotHandler};
+use crate::usb::descriptor::{
+    Endpoint, SuperSpeedEndpointCompanion, SuperSpeedPlusIsochronousEndpointCompanion, TransferType,
+};
+use crate::usb::Speed;
+use core::convert::{TryFrom, TryInto};
+
+/// The largest value the Max Endpoint Service Time Interval Payload fields can hold.
+const MAX_ESIT_PAYLOAD: u32 = 0xff_ffff;
+
+/// A builder of an Endpoint Context.
+///
+/// This struct derives the fields of an Endpoint Context from the descriptors of the endpoint and
+/// the speed of the device, following section 6.2.3 of the xHCI specification.
+///
+/// The Max Primary Streams and the Linear Stream Array fields are always set to 0, as the builder
+/// does not allocate a Stream Context Array.
+///
+/// # Examples
+///
+/// ```no_run
+/// use xhci::context::{self, EndpointContextBuilder, InputHandler};
+/// use xhci::usb::{descriptor::Endpoint, Speed};
+///
+/// # let endpoint: Endpoint = unimplemented!();
+/// # let ring_base = 0x1000;
+/// let mut input = context::Input::new_32byte();
+///
+/// EndpointContextBuilder::new(endpoint, Speed::High)
+///     .set_tr_dequeue_pointer(ring_base, true)
+///     .build(input.device_mut().endpoint_mut(endpoint.dci().into()))
+///     .expect("The endpoint descriptor is invalid.");
+/// input.control_mut().set_add_context_flag(endpoint.dci().into());
+/// ```
+#[derive(Copy, Clone, Debug)]
+#[allow(clippy::module_name_repetitions)]
+pub struct EndpointContextBuilder {
+    endpoint: Endpoint,
+    speed: Speed,
+    companion: Option<SuperSpeedEndpointCompanion>,
+    ssp_isochronous_companion: Option<SuperSpeedPlusIsochronousEndpointCompanion>,
+    max_packet_size: Option<u16>,
+    average_trb_length: Option<u16>,
+    tr_dequeue_pointer: Option<(u64, bool)>,
+}
+impl EndpointContextBuilder {
+    /// Creates a builder for the endpoint described by `endpoint` of a device running at `speed`.
+    #[must_use]
+    pub fn new(endpoint: Endpoint, speed: Speed) -> Self {
+        Self {
+            endpoint,
+            speed,
+            companion: None,
+            ssp_isochronous_companion: None,
+            max_packet_size: None,
+            average_trb_length: None,
+            tr_dequeue_pointer: None,
+        }
+    }
+
+    /// Creates a builder for the Default Control Endpoint of a device running at `speed`.
+    ///
+    /// The Max Packet Size field is set to [`Speed::default_max_packet_size`]. Use
+    /// [`EndpointContextBuilder::set_max_packet_size`] to change it.
+    #[must_use]
+    pub fn default_control_endpoint(speed: Speed) -> Self {
+        let [l, h] = speed.default_max_packet_size().to_le_bytes();
+        let endpoint = Endpoint::try_from(&[7, 5, 0, 0, l, h, 0][..]).unwrap();
+
+        Self::new(endpoint, speed)
+    }
+
+    /// Sets the SuperSpeed Endpoint Companion descriptor of the endpoint.
+    ///
+    /// The descriptor is required for all endpoints of SuperSpeed and SuperSpeedPlus devices
+    /// except the Default Control Endpoint.
+    pub fn set_companion(&mut self, companion: SuperSpeedEndpointCompanion) -> &mut Self {
+        self.companion = Some(companion);
+        self
+    }
+
+    /// Sets the SuperSpeedPlus Isochronous Endpoint Companion descriptor of the endpoint.
+    pub fn set_ssp_isochronous_companion(
+        &mut self,
+        companion: SuperSpeedPlusIsochronousEndpointCompanion,
+    ) -> &mut Self {
+        self.ssp_isochronous_companion = Some(companion);
+        self
+    }
+
+    /// Overrides the maximum packet size of the descriptor.
+    pub fn set_max_packet_size(&mut self, size: u16) -> &mut Self {
+        self.max_packet_size = Some(size);
+        self
+    }
+
+    /// Overrides the Average TRB Length field.
+    ///
+    /// By default, the field is set to 8 for control endpoints, 1024 for interrupt endpoints, and
+    /// 3072 for bulk and isochronous endpoints.
+    pub fn set_average_trb_length(&mut self, length: u16) -> &mut Self {
+        self.average_trb_length = Some(length);
+        self
+    }
+
+    /// Sets the TR Dequeue Pointer and the Dequeue Cycle State fields.
+    ///
+    /// The fields are left untouched if this method is not called.
+    pub fn set_tr_dequeue_pointer(&mut self, addr: u64, cycle_state: bool) -> &mut Self {
+        self.tr_dequeue_pointer = Some((addr, cycle_state));
+        self
+    }
+
+    /// Writes the fields to `cx`.
+    ///
+    /// `cx` is not modified if this method returns an error.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the descriptors contain a value or a combination the
+    /// specifications forbid.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the TR Dequeue Pointer is not 16-byte aligned.
+    pub fn build<E>(&self, cx: &mut E) -> Result<(), EndpointContextError>
+    where
+        E: EndpointHandler + ?Sized,
+    {
+        let f = self.fields()?;
+
+        cx.set_endpoint_type(f.endpoint_type);
+        cx.set_max_packet_size(f.max_packet_size);
+        cx.set_max_burst_size(f.max_burst_size);
+        cx.set_mult(f.mult);
+        cx.set_interval(f.interval);
+        cx.set_error_count(f.error_count);
+        cx.set_average_trb_length(f.average_trb_length);
+        cx.set_max_endpoint_service_time_interval_payload_low(
+            (f.max_esit_payload & 0xffff).try_into().unwrap(),
+        );
+        cx.set_max_endpoint_service_time_interval_payload_high(
+            (f.max_esit_payload >> 16).try_into().unwrap(),
+        );
+        cx.set_max_primary_streams(0);
+        cx.clear_linear_stream_array();
+
+        if let Some((addr, cycle_state)) = self.tr_dequeue_pointer {
+            cx.set_tr_dequeue_pointer(addr);
+            if cycle_state {
+                cx.set_dequeue_cycle_state();
+            } else {
+                cx.clear_dequeue_cycle_state();
+            }
+        }
+
+        Ok(())
+    }
+
+    fn fields(&self) -> Result<Fields, EndpointContextError> {
+        let transfer_type = self.endpoint.transfer_type();
+        let periodic = matches!(
+            transfer_type,
+            TransferType::Isochronous | TransferType::Interrupt
+        );
+
+        self.check_companions(transfer_type)?;
+
+        if self.speed == Speed::Low
+            && matches!(
+                transfer_type,
+                TransferType::Bulk | TransferType::Isochronous
+            )
+        {
+            return Err(EndpointContextError::TransferTypeNotAllowed);
+        }
+
+        let max_packet_size = self.max_packet_size();
+        self.check_max_packet_size(transfer_type, max_packet_size)?;
+
+        let (max_burst_size, mult) = self.burst_and_mult(transfer_type)?;
+
+        let max_esit_payload = if periodic {
+            self.max_esit_payload(
+                u32::from(max_packet_size)
+                    * (u32::from(max_burst_size) + 1)
+                    * (u32::from(mult) + 1),
+            )
+        } else {
+            0
+        };
+        if max_esit_payload > MAX_ESIT_PAYLOAD {
+            return Err(EndpointContextError::MaxEsitPayloadTooLarge(
+                max_esit_payload,
+            ));
+        }
+
+        let average_trb_length = self.average_trb_length.unwrap_or(match transfer_type {
+            TransferType::Control => 8,
+            TransferType::Interrupt => 1024,
+            TransferType::Bulk | TransferType::Isochronous => 3072,
+        });
+
+        Ok(Fields {
+            endpoint_type: self.endpoint.endpoint_type(),
+            max_packet_size,
+            max_burst_size,
+            mult,
+            interval: self.interval(transfer_type)?,
+            error_count: if transfer_type == TransferType::Isochronous {
+                0
+            } else {
+                3
+            },
+            average_trb_length,
+            max_esit_payload,
+        })
+    }
+
+    fn check_companions(&self, transfer_type: TransferType) -> Result<(), EndpointContextError> {
+        if self.speed.is_super_speed_or_faster() {
+            if self.companion.is_none() && transfer_type != TransferType::Control {
+                return Err(EndpointContextError::MissingCompanion);
+            }
+        } else if self.companion.is_some() {
+            return Err(EndpointContextError::UnexpectedCompanion);
+        }
+
+        let ssp_expected = self.speed == Speed::SuperSpeedPlus
+            && transfer_type == TransferType::Isochronous
+            && self
+                .companion
+                .is_some_and(|c| c.ssp_isochronous_companion());
+        match (ssp_expected, self.ssp_isochronous_companion.is_some()) {
+            (true, false) => Err(EndpointContextError::MissingCompanion),
+            (false, true) => Err(EndpointContextError::UnexpectedCompanion),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_max_packet_size(
+        &self,
+        transfer_type: TransferType,
+        size: u16,
+    ) -> Result<(), EndpointContextError> {
+        let ok = match (self.speed, transfer_type) {
+            (Speed::Low, _) => size <= 8,
+            (Speed::Full, TransferType::Control | TransferType::Bulk) => {
+                matches!(size, 8 | 16 | 32 | 64)
+            }
+            (Speed::Full, TransferType::Interrupt) => size <= 64,
+            (Speed::Full, TransferType::Isochronous) => size <= 1023,
+            (Speed::High, TransferType::Control) => size == 64,
+            (Speed::High, TransferType::Bulk) => size == 512,
+            (Speed::High, _) => size <= 1024,
+            (_, TransferType::Control) => size == 512,
+            (_, TransferType::Bulk) => size == 1024,
+            (_, _) => size <= 1024,
+        };
+
+        if ok {
+            Ok(())
+        } else {
+            Err(EndpointContextError::InvalidMaxPacketSize(size))
+        }
+    }
+
+    fn burst_and_mult(
+        &self,
+        transfer_type: TransferType,
+    ) -> Result<(u8, u8), EndpointContextError> {
+        if let Some(c) = self.companion {
+            if c.max_burst() > 15 {
+                return Err(EndpointContextError::InvalidMaxBurst(c.max_burst()));
+            }
+
+            let mult = if transfer_type == TransferType::Isochronous
+                && self.ssp_isochronous_companion.is_none()
+            {
+                c.mult()
+            } else {
+                0
+            };
+            if mult > 2 {
+                return Err(EndpointContextError::InvalidMult(mult));
+            }
+
+            Ok((c.max_burst(), mult))
+        } else if self.speed == Speed::High
+            && matches!(
+                transfer_type,
+                TransferType::Isochronous | TransferType::Interrupt
+            )
+        {
+            let t = self.endpoint.additional_transactions();
+            if t > 2 {
+                return Err(EndpointContextError::InvalidMaxBurst(t));
+            }
+
+            Ok((t, 0))
+        } else {
+            Ok((0, 0))
+        }
+    }
+
+    /// Returns the Max Endpoint Service Time Interval Payload. `max_bytes` is the value
+    /// calculated from the maximum packet size and the number of packets per interval, which is
+    /// used if no companion descriptor specifies the payload.
+    fn max_esit_payload(&self, max_bytes: u32) -> u32 {
+        if let Some(c) = self.ssp_isochronous_companion {
+            c.bytes_per_interval()
+        } else if let Some(c) = self.companion {
+            c.bytes_per_interval().into()
+        } else {
+            max_bytes
+        }
+    }
+
+    /// Converts the bInterval field into the Interval field, which is the exponent of the period
+    /// in 125 us units.
+    fn interval(&self, transfer_type: TransferType) -> Result<u8, EndpointContextError> {
+        let b = self.endpoint.interval();
+        let invalid = Err(EndpointContextError::InvalidInterval(b));
+
+        match (self.speed, transfer_type) {
+            (_, TransferType::Control | TransferType::Bulk) => Ok(0),
+            (Speed::Low | Speed::Full, TransferType::Interrupt) => {
+                if b == 0 {
+                    invalid
+                } else {
+                    let frames = 7 - u8::try_from(b.leading_zeros()).unwrap();
+                    Ok((frames + 3).clamp(3, 10))
+                }
+            }
+            (Speed::Full, TransferType::Isochronous) => {
+                if (1..=16).contains(&b) {
+                    Ok(b - 1 + 3)
+                } else {
+                    invalid
+                }
+            }
+            _ => {
+                if (1..=16).contains(&b) {
+                    Ok(b - 1)
+                } else {
+                    invalid
+                }
+            }
+        }
+    }
+
+    fn max_packet_size(&self) -> u16 {
+        self.max_packet_size
+            .unwrap_or_else(|| self.endpoint.max_packet_size())
+    }
+}
+
+/// An error returned when the descriptors of an endpoint describe an invalid configuration.
+#[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[allow(clippy::module_name_repetitions)]
+pub enum EndpointContextError {
+    /// The endpoint of a SuperSpeed or faster device lacks a required companion descriptor.
+    MissingCompanion,
+    /// A companion descriptor is given although the speed or the transfer type does not use it.
+    UnexpectedCompanion,
+    /// The transfer type is not allowed at the speed, such as a bulk endpoint of a low-speed
+    /// device.
+    TransferTypeNotAllowed,
+    /// The maximum packet size is not allowed for the speed and the transfer type.
+    InvalidMaxPacketSize(u16),
+    /// The bMaxBurst field or the number of additional transactions is out of range.
+    InvalidMaxBurst(u8),
+    /// The Mult field of the SuperSpeed Endpoint Companion descriptor is reserved.
+    InvalidMult(u8),
+    /// The bInterval field is out of range.
+    InvalidInterval(u8),
+    /// The Max Endpoint Service Time Interval Payload does not fit in 24 bits.
+    MaxEsitPayloadTooLarge(u32),
+}
+
+/// A builder of a Slot Context.
+///
+/// The builder takes the topology path from the Root Hub Port to the device, and fills the Route
+/// String, the Speed, and the fields of the Transaction Translator (TT) which a low- or full-speed
+/// device behind a high-speed hub uses.
+///
+/// # Examples
+///
+/// ```no_run
+/// use xhci::context::{self, Hub, InputHandler, SlotContextBuilder};
+/// use xhci::usb::Speed;
+///
+/// # let hub_slot_id = 1;
+/// let mut input = context::Input::new_32byte();
+///
+/// // A full-speed device connected to the port 2 of a high-speed hub, which is connected to the
+/// // Root Hub Port 3.
+/// SlotContextBuilder::new(3, Speed::Full)
+///     .attach_to(Hub::new(hub_slot_id, Speed::High), 2)
+///     .expect("Invalid topology.")
+///     .build(input.device_mut().slot_mut())
+///     .expect("Invalid Slot Context.");
+/// input.control_mut().set_add_context_flag(0);
+/// ```
+#[derive(Copy, Clone, Debug)]
+#[allow(clippy::module_name_repetitions)]
+pub struct SlotContextBuilder {
+    root_hub_port_number: u8,
+    speed: Speed,
+    route_string: u32,
+    depth: usize,
+    parent: Option<(Hub, u8)>,
+    tt_hub: Option<(Hub, u8)>,
+    number_of_ports: Option<u8>,
+    transaction_translator: Option<(u8, bool)>,
+    context_entries: u8,
+    max_exit_latency: u16,
+    interrupter_target: u16,
+}
+impl SlotContextBuilder {
+    /// Creates a builder for a device running at `speed` whose path starts at the Root Hub Port
+    /// `root_hub_port_number`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `root_hub_port_number == 0`.
+    #[must_use]
+    pub fn new(root_hub_port_number: u8, speed: Speed) -> Self {
+        assert_ne!(
+            root_hub_port_number, 0,
+            "The Root Hub Port Number must not be 0."
+        );
+
+        Self {
+            root_hub_port_number,
+            speed,
+            route_string: 0,
+            depth: 0,
+            parent: None,
+            tt_hub: None,
+            number_of_ports: None,
+            transaction_translator: None,
+            context_entries: 1,
+            max_exit_latency: 0,
+            interrupter_target: 0,
+        }
+    }
+
+    /// Appends a hub to the topology path.
+    ///
+    /// Call this method for each hub between the Root Hub Port and the device, starting from the
+    /// hub connected to the Root Hub Port. `port` is the downstream port of `hub` which the next
+    /// hub or the device is connected to.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the path already contains [`MAX_HUB_DEPTH`] hubs, or if
+    /// `hub` is slower than the device or the previous hub.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `port == 0`.
+    pub fn attach_to(&mut self, hub: Hub, port: u8) -> Result<&mut Self, SlotContextError> {
+        assert_ne!(port, 0, "The port number must not be 0.");
+
+        if self.depth == MAX_HUB_DEPTH {
+            return Err(SlotContextError::TooDeep);
+        }
+        if hub.speed < self.speed || self.parent.is_some_and(|(p, _)| p.speed < hub.speed) {
+            return Err(SlotContextError::SpeedMismatch);
+        }
+
+        self.route_string |= u32::from(port.min(15)) << (self.depth * 4);
+        self.depth += 1;
+        self.parent = Some((hub, port));
+        if hub.speed == Speed::High && self.speed < Speed::High {
+            self.tt_hub = Some((hub, port));
+        }
+
+        Ok(self)
+    }
+
+    /// Marks the device as a hub with `number_of_ports` downstream ports.
+    pub fn set_hub(&mut self, number_of_ports: u8) -> &mut Self {
+        self.number_of_ports = Some(number_of_ports);
+        self
+    }
+
+    /// Sets the TT Think Time field and the Multi-TT bit of a high-speed hub.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `think_time > 3`.
+    pub fn set_transaction_translator(&mut self, think_time: u8, multi_tt: bool) -> &mut Self {
+        assert!(think_time <= 3, "The TT Think Time must be less than 4.");
+
+        self.transaction_translator = Some((think_time, multi_tt));
+        self
+    }
+
+    /// Notifies the builder that the endpoint of `dci` is enabled.
+    ///
+    /// The Context Entries field is set to the largest DCI passed to this method, or 1 if this
+    /// method is never called.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `dci` is not within 1..=31.
+    pub fn enable_endpoint(&mut self, dci: u8) -> &mut Self {
+        assert!((1..=31).contains(&dci), "The DCI must be within 1..=31.");
+
+        self.context_entries = self.context_entries.max(dci);
+        self
+    }
+
+    /// Sets the Max Exit Latency field.
+    pub fn set_max_exit_latency(&mut self, latency: u16) -> &mut Self {
+        self.max_exit_latency = latency;
+        self
+    }
+
+    /// Sets the Interrupter Target field.
+    pub fn set_interrupter_target(&mut self, target: u16) -> &mut Self {
+        self.interrupter_target = target;
+        self
+    }
+
+    /// Writes the fields to `cx`.
+    ///
+    /// `cx` is not modified if this method returns an error.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`SlotContextError::UnexpectedTransactionTranslator`] if
+    /// [`SlotContextBuilder::set_transaction_translator`] is called for a device which is not a
+    /// high-speed hub.
+    pub fn build<S>(&self, cx: &mut S) -> Result<(), SlotContextError>
+    where
+        S: SlotHandler + ?Sized,
+    {
+        let is_hub = self.number_of_ports.is_some();
+        if self.transaction_translator.is_some() && !(is_hub && self.speed == Speed::High) {
+            return Err(SlotContextError::UnexpectedTransactionTranslator);
+        }
+
+        cx.set_route_string(self.route_string);
+        cx.set_speed(self.speed.default_port_speed());
+        cx.set_context_entries(self.context_entries);
+        cx.set_max_exit_latency(self.max_exit_latency);
+        cx.set_root_hub_port_number(self.root_hub_port_number);
+        cx.set_interrupter_target(self.interrupter_target);
+
+        if let Some(n) = self.number_of_ports {
+            cx.set_hub();
+            cx.set_number_of_ports(n);
+        } else {
+            cx.clear_hub();
+            cx.set_number_of_ports(0);
+        }
+
+        let (think_time, multi_tt) = self.transaction_translator.unwrap_or_default();
+        cx.set_tt_think_time(think_time);
+
+        if let Some((hub, port)) = self.tt_hub {
+            cx.set_parent_hub_slot_id(hub.slot_id);
+            cx.set_parent_port_number(port);
+        } else {
+            cx.set_parent_hub_slot_id(0);
+            cx.set_parent_port_number(0);
+        }
+
+        if multi_tt || self.tt_hub.is_some_and(|(h, _)| h.multi_tt) {
+            cx.set_multi_tt();
+        } else {
+            cx.clear_multi_tt();
+        }
+
+        Ok(())
+    }
+}
+
+/// The maximum number of hubs between a Root Hub Port and a device.
+pub const MAX_HUB_DEPTH: usize = 5;
+
+/// A hub in the topology path of a device.
+#[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct Hub {
+    slot_id: u8,
+    speed: Speed,
+    multi_tt: bool,
+}
+impl Hub {
+    /// Creates a hub whose Slot ID is `slot_id` and which runs at `speed`.
+    #[must_use]
+    pub fn new(slot_id: u8, speed: Speed) -> Self {
+        Self {
+            slot_id,
+            speed,
+            multi_tt: false,
+        }
+    }
+
+    /// Marks the hub as a high-speed hub which has a TT for each port.
+    pub fn set_multi_tt(&mut self) -> &mut Self {
+        self.multi_tt = true;
+        self
+    }
+
+    /// Returns the Slot ID of the hub.
+    #[must_use]
+    pub fn slot_id(&self) -> u8 {
+        self.slot_id
+    }
+
+    /// Returns the speed of the hub.
+    #[must_use]
+    pub fn speed(&self) -> Speed {
+        self.speed
+    }
+
+    /// Returns `true` if the hub has a TT for each port.
+    #[must_use]
+    pub fn multi_tt(&self) -> bool {
+        self.multi_tt
+    }
+}
+
+/// An error returned when a Slot Context cannot be built.
+#[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Hash)]
+#[allow(clippy::module_name_repetitions)]
+pub enum SlotContextError {
+    /// The topology path contains more than [`MAX_HUB_DEPTH`] hubs.
+    TooDeep,
+    /// A hub in the path is slower than the device or the hub after it.
+    SpeedMismatch,
+    /// The TT fields are given for a device which is not a high-speed hub.
+    UnexpectedTransactionTranslator,
+}
+
+struct Fields {
+    endpoint_type: EndpointType,
+    max_packet_size: u16,
+    max_burst_size: u8,
+    mult: u8,
+    interval: u8,
+    error_count: u8,
+    average_trb_length: u16,
+    max_esit_payload: u32,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::context::{Endpoint32Byte, Slot32Byte};
+
+    fn endpoint(bytes: [u8; 7]) -> Endpoint {
+        Endpoint::try_from(&bytes[..]).unwrap()
+    }
+
+    #[test]
+    fn high_speed_interrupt_endpoint() {
+        // IN, interrupt, 64 bytes with 2 additional transactions, every 8 microframes.
+        let e = endpoint([7, 5, 0x81, 3, 0x40, 0x10, 4]);
+        let mut cx = Endpoint32Byte::default();
+        EndpointContextBuilder::new(e, Speed::High)
+            .build(&mut cx)
+            .unwrap();
+
+        assert_eq!(cx.endpoint_type(), EndpointType::InterruptIn);
+        assert_eq!(cx.max_packet_size(), 64);
+        assert_eq!(cx.max_burst_size(), 2);
+        assert_eq!(cx.interval(), 3);
+        assert_eq!(cx.error_count(), 3);
+        assert_eq!(cx.max_endpoint_service_time_interval_payload_low(), 192);
+    }
+
+    #[test]
+    fn full_speed_interrupt_interval_is_converted_to_microframes() {
+        let e = endpoint([7, 5, 0x81, 3, 8, 0, 10]);
+        let mut cx = Endpoint32Byte::default();
+        EndpointContextBuilder::new(e, Speed::Full)
+            .build(&mut cx)
+            .unwrap();
+
+        assert_eq!(cx.interval(), 6);
+    }
+
+    #[test]
+    fn super_speed_endpoint_requires_companion() {
+        let e = endpoint([7, 5, 0x02, 2, 0, 4, 0]);
+        let mut cx = Endpoint32Byte::default();
+        let mut b = EndpointContextBuilder::new(e, Speed::SuperSpeed);
+        assert_eq!(
+            b.build(&mut cx),
+            Err(EndpointContextError::MissingCompanion)
+        );
+
+        let c = SuperSpeedEndpointCompanion::try_from(&[6, 48, 15, 0, 0, 0][..]).unwrap();
+        b.set_companion(c).build(&mut cx).unwrap();
+        assert_eq!(cx.max_burst_size(), 15);
+        assert_eq!(cx.endpoint_type(), EndpointType::BulkOut);
+    }
+
+    #[test]
+    fn low_speed_bulk_endpoint_is_rejected() {
+        let e = endpoint([7, 5, 0x02, 2, 8, 0, 0]);
+        let mut cx = Endpoint32Byte::default();
+        assert_eq!(
+            EndpointContextBuilder::new(e, Speed::Low).build(&mut cx),
+            Err(EndpointContextError::TransferTypeNotAllowed)
+        );
+    }
+
+    #[test]
+    fn full_speed_device_behind_high_speed_hub_uses_tt() {
+        let mut hs = Hub::new(2, Speed::High);
+        hs.set_multi_tt();
+
+        let mut cx = Slot32Byte::default();
+        SlotContextBuilder::new(3, Speed::Full)
+            .attach_to(Hub::new(1, Speed::SuperSpeed), 4)
+            .unwrap()
+            .attach_to(hs, 17)
+            .unwrap()
+            .attach_to(Hub::new(5, Speed::Full), 1)
+            .unwrap()
+            .enable_endpoint(3)
+            .build(&mut cx)
+            .unwrap();
+
+        assert_eq!(cx.route_string(), 0x1f4);
+        assert_eq!(cx.root_hub_port_number(), 3);
+        assert_eq!(cx.speed(), 1);
+        assert_eq!(cx.parent_hub_slot_id(), 2);
+        assert_eq!(cx.parent_port_number(), 17);
+        assert!(cx.multi_tt());
+        assert_eq!(cx.context_entries(), 3);
+    }
+
+    #[test]
+    fn path_deeper_than_five_hubs_is_rejected() {
+        let mut b = SlotContextBuilder::new(1, Speed::High);
+        for _ in 0..MAX_HUB_DEPTH {
+            b.attach_to(Hub::new(1, Speed::High), 1).unwrap();
+        }
+
+        assert_eq!(
+            b.attach_to(Hub::new(1, Speed::High), 1).err(),
+            Some(SlotContextError::TooDeep)
+        );
+    }
+}
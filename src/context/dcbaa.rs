@@ -0,0 +1,246 @@
+//! Device Context Base Address Array and Scratchpad Buffer Array.
+
+use crate::dma::{Allocator, DmaBox, Requirement};
+use crate::registers::capability::StructuralParameters2;
+use crate::registers::operational::PageSizeRegister;
+use core::convert::TryFrom;
+use core::ptr;
+
+/// Device Context Base Address Array.
+///
+/// The array has `MaxSlots` + 1 entries. The 0th entry is the pointer to the Scratchpad Buffer
+/// Array, and the `n`th entry is the pointer to the Device Context of the Device Slot `n`. Write
+/// [`Dcbaa::phys_addr`] to the Device Context Base Address Array Pointer Register.
+#[derive(Debug)]
+pub struct Dcbaa<A>
+where
+    A: Allocator + Clone,
+{
+    entries: DmaBox<[u64], A>,
+}
+impl<A> Dcbaa<A>
+where
+    A: Allocator + Clone,
+{
+    /// Allocates a new array for `max_slots` Device Slots, filled with 0.
+    ///
+    /// Pass the value of the Max Device Slots Enabled field of the Configure Register as
+    /// `max_slots`.
+    ///
+    /// This method returns [`None`] if the allocator fails to allocate memory.
+    pub fn new(max_slots: u8, allocator: A, page_size: PageSizeRegister) -> Option<Self> {
+        let entries = DmaBox::new_slice(
+            usize::from(max_slots) + 1,
+            allocator,
+            Requirement::device_context_base_address_array(page_size.bytes()),
+        )?;
+
+        Some(Self { entries })
+    }
+
+    /// Returns the physical address of the array.
+    #[must_use]
+    pub fn phys_addr(&self) -> u64 {
+        self.entries.phys_addr()
+    }
+
+    /// Returns the number of Device Slots this array contains.
+    #[must_use]
+    pub fn max_slots(&self) -> u8 {
+        u8::try_from(self.entries.len() - 1).unwrap()
+    }
+
+    /// Returns the pointer to the Device Context of the Device Slot `slot_id`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `slot_id` is 0 or greater than the number of Device Slots.
+    #[must_use]
+    pub fn device_context(&self, slot_id: u8) -> u64 {
+        let i = self.ensure_slot_id_is_valid(slot_id);
+
+        // SAFETY: `i` is within the array.
+        unsafe { ptr::read_volatile(ptr::addr_of!(self.entries[i])) }
+    }
+
+    /// Sets the pointer to the Device Context of the Device Slot `slot_id`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `slot_id` is 0 or greater than the number of Device Slots, or if
+    /// `addr` is not 64-byte aligned.
+    pub fn set_device_context(&mut self, slot_id: u8, addr: u64) {
+        let i = self.ensure_slot_id_is_valid(slot_id);
+        assert!(
+            addr.trailing_zeros() >= 6,
+            "The Device Context must be 64-byte aligned."
+        );
+
+        self.write(i, addr);
+    }
+
+    /// Clears the pointer to the Device Context of the Device Slot `slot_id`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `slot_id` is 0 or greater than the number of Device Slots.
+    pub fn clear_device_context(&mut self, slot_id: u8) {
+        let i = self.ensure_slot_id_is_valid(slot_id);
+
+        self.write(i, 0);
+    }
+
+    /// Returns the pointer to the Scratchpad Buffer Array.
+    #[must_use]
+    pub fn scratchpad_buffer_array(&self) -> u64 {
+        // SAFETY: The array has at least one entry.
+        unsafe { ptr::read_volatile(ptr::addr_of!(self.entries[0])) }
+    }
+
+    /// Sets the pointer to the Scratchpad Buffer Array.
+    ///
+    /// The xHC may access `array` while it is running, so `array` must outlive the running xHC.
+    pub fn set_scratchpad_buffer_array(&mut self, array: &ScratchpadBufferArray<A>) {
+        self.write(0, array.phys_addr());
+    }
+
+    fn write(&mut self, i: usize, v: u64) {
+        // SAFETY: `i` is within the array.
+        unsafe { ptr::write_volatile(ptr::addr_of_mut!(self.entries[i]), v) };
+    }
+
+    fn ensure_slot_id_is_valid(&self, slot_id: u8) -> usize {
+        assert!(
+            (1..=self.max_slots()).contains(&slot_id),
+            "The Slot ID must be within 1..=MaxSlots."
+        );
+
+        slot_id.into()
+    }
+}
+
+/// Scratchpad Buffer Array and the Scratchpad Buffers.
+///
+/// The xHC uses the Scratchpad Buffers as its private memory. Each entry of the array points to a
+/// buffer of the size of a page.
+#[derive(Debug)]
+pub struct ScratchpadBufferArray<A>
+where
+    A: Allocator + Clone,
+{
+    array: DmaBox<[u64], A>,
+    _buffers: DmaBox<[u8], A>,
+}
+impl<A> ScratchpadBufferArray<A>
+where
+    A: Allocator + Clone,
+{
+    /// Allocates the array and as many buffers as the Max Scratchpad Buffers field of
+    /// `hcsparams2` specifies.
+    ///
+    /// `page_size` is the value of the Page Size Register. All buffers are page-aligned.
+    ///
+    /// This method returns [`None`] if the allocator fails to allocate memory.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the xHC requires no Scratchpad Buffers.
+    pub fn new(
+        hcsparams2: StructuralParameters2,
+        page_size: PageSizeRegister,
+        allocator: A,
+    ) -> Option<Self> {
+        let len = usize::try_from(hcsparams2.max_scratchpad_buffers()).unwrap();
+        assert_ne!(len, 0, "The xHC requires no Scratchpad Buffers.");
+
+        let page_size = page_size.bytes();
+
+        let mut array = DmaBox::<[u64], _>::new_slice(
+            len,
+            allocator.clone(),
+            Requirement::SCRATCHPAD_BUFFER_ARRAY,
+        )?;
+        // All buffers are allocated at once. Each page of this block is a buffer, so that each
+        // buffer meets `Requirement::scratchpad_buffer`.
+        let buffers = DmaBox::<[u8], _>::new_slice(
+            page_size * len,
+            allocator,
+            Requirement::new(page_size, 0),
+        )?;
+
+        for (i, a) in array.iter_mut().enumerate() {
+            *a = buffers.phys_addr() + u64::try_from(i * page_size).unwrap();
+        }
+
+        Some(Self {
+            array,
+            _buffers: buffers,
+        })
+    }
+
+    /// Returns the physical address of the array.
+    #[must_use]
+    pub fn phys_addr(&self) -> u64 {
+        self.array.phys_addr()
+    }
+
+    /// Returns the number of the Scratchpad Buffers.
+    #[must_use]
+    pub fn number_of_buffers(&self) -> usize {
+        self.array.len()
+    }
+
+    /// Returns the physical address of the `i`th Scratchpad Buffer.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `i` is not less than the number of the buffers.
+    #[must_use]
+    pub fn buffer(&self, i: usize) -> u64 {
+        self.array[i]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dma::Heap;
+
+    fn page_size_4k() -> PageSizeRegister {
+        PageSizeRegister::from_raw(1)
+    }
+
+    #[test]
+    fn dcbaa_points_to_scratchpads() {
+        // Max Scratchpad Buffers Lo = 3.
+        let hcsparams2 = StructuralParameters2::from_raw(3 << 27);
+
+        let mut dcbaa = Dcbaa::new(8, Heap, page_size_4k()).unwrap();
+        let scratchpads = ScratchpadBufferArray::new(hcsparams2, page_size_4k(), Heap).unwrap();
+        dcbaa.set_scratchpad_buffer_array(&scratchpads);
+        dcbaa.set_device_context(8, 0x1_0040);
+
+        assert_eq!(dcbaa.max_slots(), 8);
+        assert_eq!(dcbaa.scratchpad_buffer_array(), scratchpads.phys_addr());
+        assert_eq!(dcbaa.device_context(8), 0x1_0040);
+        assert_eq!(scratchpads.number_of_buffers(), 3);
+        assert_eq!(scratchpads.buffer(2) - scratchpads.buffer(0), 0x2000);
+        assert_eq!(scratchpads.buffer(0) % 0x1000, 0);
+    }
+
+    #[test]
+    fn array_may_exceed_page() {
+        // Max Scratchpad Buffers Hi = 31 and Lo = 31.
+        let hcsparams2 = StructuralParameters2::from_raw(31 << 21 | 31 << 27);
+
+        let scratchpads = ScratchpadBufferArray::new(hcsparams2, page_size_4k(), Heap).unwrap();
+        assert_eq!(scratchpads.number_of_buffers(), 1023);
+    }
+
+    #[test]
+    #[should_panic(expected = "The Slot ID must be within 1..=MaxSlots.")]
+    fn slot_0_is_rejected() {
+        let mut dcbaa = Dcbaa::new(8, Heap, page_size_4k()).unwrap();
+        dcbaa.set_device_context(0, 0x1000);
+    }
+}
@@ -26,12 +26,23 @@
 #[macro_use]
 mod macros;
 
+mod any;
+mod builder;
+mod dcbaa;
+
 use bit_field::BitField;
 use core::convert::TryInto;
 use core::fmt;
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 
+pub use any::{AnyDevice, AnyInput};
+pub use builder::{
+    EndpointContextBuilder, EndpointContextError, Hub, SlotContextBuilder, SlotContextError,
+    MAX_HUB_DEPTH,
+};
+pub use dcbaa::{Dcbaa, ScratchpadBufferArray};
+
 /// The number of Endpoint Contexts in a Device Context.
 pub const NUM_OF_ENDPOINT_CONTEXTS: usize = 31;
 
@@ -499,9 +510,9 @@ pub trait EndpointHandler: AsRef<[u32]> + AsMut<[u32]> {
     ///
     /// # Panics
     ///
-    /// This method panics if `addr` is not 64-byte aligned.
+    /// This method panics if `addr` is not 16-byte aligned.
     fn set_tr_dequeue_pointer(&mut self, a: u64) {
-        assert_eq!(a % 64, 0, "TR Dequeue Pointer must be 64-byte aligned.");
+        assert_eq!(a % 16, 0, "TR Dequeue Pointer must be 16-byte aligned.");
 
         let l: u32 = a.get_bits(0..32).try_into().unwrap();
         let u: u32 = a.get_bits(32..64).try_into().unwrap();
@@ -0,0 +1,467 @@
+//! Host Controller initialization.
+//!
+//! [`Initializer`] performs the initialization sequence described in section 4.2 of the xHCI
+//! specification and returns a running [`Controller`], which owns the Command Ring and the Event
+//! Ring of the primary interrupter.
+//!
+//! The memory which the xHC accesses is obtained through [`Allocator`], and timeouts are measured
+//! with [`Clock`], so that this module works without `std`.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use core::{alloc::Layout, num::NonZeroUsize, time::Duration};
+//! # use xhci::accessor::Mapper;
+//! # use xhci::controller::{Clock, Initializer};
+//! # use xhci::dma::Allocator;
+//! #
+//! # const MMIO_BASE: usize = 0x1000;
+//! #
+//! # #[derive(Clone)]
+//! # struct MemoryMapper;
+//! # impl Mapper for MemoryMapper {
+//! #     unsafe fn map(&mut self, phys_base: usize, bytes: usize) -> NonZeroUsize {
+//! #         unimplemented!()
+//! #     }
+//! #
+//! #     fn unmap(&mut self, virt_base: usize, bytes: usize) {
+//! #         unimplemented!()
+//! #     }
+//! # }
+//! #
+//! # #[derive(Clone)]
+//! # struct DmaAllocator;
+//! # unsafe impl Allocator for DmaAllocator {
+//! #     fn allocate(&mut self, layout: Layout, boundary: usize) -> Option<(usize, u64)> {
+//! #         unimplemented!()
+//! #     }
+//! #
+//! #     unsafe fn deallocate(&mut self, virt: usize, layout: Layout) {
+//! #         unimplemented!()
+//! #     }
+//! # }
+//! #
+//! # struct Timer;
+//! # impl Clock for Timer {
+//! #     fn now(&self) -> Duration {
+//! #         unimplemented!()
+//! #     }
+//! # }
+//! #
+//! let mut r = unsafe { xhci::Registers::new(MMIO_BASE, MemoryMapper) };
+//!
+//! let mut initializer = Initializer::new(DmaAllocator, Timer);
+//! initializer.set_timeout(Duration::from_millis(100));
+//!
+//! let mut controller = initializer
+//!     .initialize(&mut r)
+//!     .expect("Failed to initialize the xHC.");
+//!
+//! for event in controller.event_ring() {
+//!     // Handle events.
+//! }
+//! ```
+
+use crate::context::{Dcbaa, ScratchpadBufferArray};
+use crate::dma::{Allocator, DmaBox, Requirement};
+use crate::interrupter::{self, EventRingMemory};
+use crate::registers::Registers;
+use crate::ring::event::{EventRing, EventRingSegmentTableEntry};
+use crate::ring::producer::ProducerRing;
+use crate::ring::trb::command;
+use crate::ring::Segment;
+use accessor::Mapper;
+use core::time::Duration;
+
+/// A source of the current time.
+pub trait Clock {
+    /// Returns the time elapsed since an arbitrary fixed point.
+    ///
+    /// The returned value must not decrease.
+    fn now(&self) -> Duration;
+}
+
+/// The builder which initializes the xHC.
+#[derive(Debug)]
+pub struct Initializer<A, C>
+where
+    A: Allocator + Clone,
+    C: Clock,
+{
+    allocator: A,
+    clock: C,
+    timeout: Duration,
+    max_device_slots: u8,
+    command_ring_size: usize,
+    event_ring_size: u16,
+}
+impl<A, C> Initializer<A, C>
+where
+    A: Allocator + Clone,
+    C: Clock,
+{
+    /// Creates a new initializer.
+    ///
+    /// By default, the timeout of each waiting step is 1 second, all Device Slots the xHC
+    /// supports are enabled, and both the Command Ring and the Event Ring contain 256 TRBs.
+    #[must_use]
+    pub fn new(allocator: A, clock: C) -> Self {
+        Self {
+            allocator,
+            clock,
+            timeout: Duration::from_secs(1),
+            max_device_slots: u8::MAX,
+            command_ring_size: 256,
+            event_ring_size: 256,
+        }
+    }
+
+    /// Sets the time to wait for the xHC in each step of the initialization.
+    pub fn set_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the maximum number of Device Slots to enable.
+    ///
+    /// If `n` is greater than the number of Device Slots the xHC supports, the latter is used.
+    pub fn set_max_device_slots(&mut self, n: u8) -> &mut Self {
+        self.max_device_slots = n;
+        self
+    }
+
+    /// Sets the number of TRBs the Command Ring contains, including the Link TRB.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `trbs` is not within `3..=4096`.
+    pub fn set_command_ring_size(&mut self, trbs: usize) -> &mut Self {
+        assert!(
+            (3..=4096).contains(&trbs),
+            "The Command Ring must contain 3 to 4096 TRBs."
+        );
+
+        self.command_ring_size = trbs;
+        self
+    }
+
+    /// Sets the number of TRBs the Event Ring contains.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `trbs` is not within `16..=4096`.
+    pub fn set_event_ring_size(&mut self, trbs: u16) -> &mut Self {
+        assert!(
+            (EventRingSegmentTableEntry::MIN_RING_SEGMENT_SIZE
+                ..=EventRingSegmentTableEntry::MAX_RING_SEGMENT_SIZE)
+                .contains(&trbs),
+            "The Event Ring must contain 16 to 4096 TRBs."
+        );
+
+        self.event_ring_size = trbs;
+        self
+    }
+
+    /// Initializes the xHC and makes it run.
+    ///
+    /// This method waits until the xHC becomes ready, halts and resets it, sets the Max Device
+    /// Slots Enabled field, the Device Context Base Address Array, the Scratchpad Buffers, the
+    /// Command Ring, and the Event Ring of the primary interrupter, and finally sets the Run/Stop
+    /// bit. Interrupts are not enabled.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`Error::Timeout`] if the xHC does not respond within the timeout, and
+    /// [`Error::AllocationFailed`] if the allocator fails to allocate memory.
+    pub fn initialize<M>(mut self, r: &mut Registers<M>) -> Result<Controller<A>, Error>
+    where
+        M: Mapper + Clone,
+    {
+        self.reset(r)?;
+
+        let max_device_slots = self.max_device_slots.min(
+            r.capability
+                .hcsparams1
+                .read_volatile()
+                .number_of_device_slots(),
+        );
+        r.operational.config.update_volatile(|c| {
+            c.set_max_device_slots_enabled(max_device_slots);
+        });
+
+        let page_size = r.operational.pagesize.read_volatile();
+        let context_size = if r.capability.hccparams1.read_volatile().context_size() {
+            64
+        } else {
+            32
+        };
+
+        let mut dcbaa = Dcbaa::new(max_device_slots, self.allocator.clone(), page_size)
+            .ok_or(Error::AllocationFailed)?;
+
+        let hcsparams2 = r.capability.hcsparams2.read_volatile();
+        let scratchpad = if hcsparams2.max_scratchpad_buffers() == 0 {
+            None
+        } else {
+            let array = ScratchpadBufferArray::new(hcsparams2, page_size, self.allocator.clone())
+                .ok_or(Error::AllocationFailed)?;
+            dcbaa.set_scratchpad_buffer_array(&array);
+
+            Some(array)
+        };
+
+        r.operational.dcbaap.update_volatile(|d| {
+            d.set(dcbaa.phys_addr());
+        });
+
+        let (command_ring, command_ring_memory) = self.init_command_ring(r)?;
+        let (event_ring, event_ring_memory) = self.init_event_ring(r)?;
+
+        r.operational.usbcmd.update_volatile(|u| {
+            u.set_run_stop();
+        });
+        self.wait_until(Stage::Run, || {
+            !r.operational.usbsts.read_volatile().hc_halted()
+        })?;
+
+        Ok(Controller {
+            allocator: self.allocator,
+            max_device_slots,
+            page_size: page_size.bytes(),
+            context_size,
+            dcbaa,
+            _scratchpad: scratchpad,
+            command_ring,
+            _command_ring_memory: command_ring_memory,
+            event_ring,
+            _event_ring_memory: event_ring_memory,
+        })
+    }
+
+    fn reset<M>(&self, r: &mut Registers<M>) -> Result<(), Error>
+    where
+        M: Mapper + Clone,
+    {
+        self.wait_until(Stage::ControllerNotReady, || {
+            !r.operational.usbsts.read_volatile().controller_not_ready()
+        })?;
+
+        r.operational.usbcmd.update_volatile(|u| {
+            u.clear_run_stop();
+        });
+        self.wait_until(Stage::Halt, || {
+            r.operational.usbsts.read_volatile().hc_halted()
+        })?;
+
+        r.operational.usbcmd.update_volatile(|u| {
+            u.set_host_controller_reset();
+        });
+        self.wait_until(Stage::Reset, || {
+            !r.operational.usbcmd.read_volatile().host_controller_reset()
+                && !r.operational.usbsts.read_volatile().controller_not_ready()
+        })?;
+
+        Ok(())
+    }
+
+    fn init_command_ring<M>(
+        &mut self,
+        r: &mut Registers<M>,
+    ) -> Result<(ProducerRing<command::Allowed, 1>, SegmentMemory<A>), Error>
+    where
+        M: Mapper + Clone,
+    {
+        let memory =
+            self.allocate_slice(self.command_ring_size, Requirement::COMMAND_RING_SEGMENT)?;
+        // SAFETY: `memory` is owned by the returned `Controller` together with the ring.
+        let command_ring = ProducerRing::new([unsafe {
+            Segment::new(memory.virt_addr(), memory.phys_addr(), memory.len())
+        }]);
+
+        r.operational.crcr.update_volatile(|c| {
+            c.set_command_ring_pointer(command_ring.enqueue_pointer());
+            if command_ring.cycle_state() {
+                c.set_ring_cycle_state();
+            } else {
+                c.clear_ring_cycle_state();
+            }
+        });
+
+        Ok((command_ring, memory))
+    }
+
+    fn init_event_ring<M>(
+        &mut self,
+        r: &mut Registers<M>,
+    ) -> Result<(EventRing<1>, EventRingMemory<A>), Error>
+    where
+        M: Mapper + Clone,
+    {
+        let (event_ring, memory) =
+            interrupter::new_event_ring(&self.allocator, self.event_ring_size)
+                .ok_or(Error::AllocationFailed)?;
+        interrupter::install(r, 0, &event_ring);
+
+        Ok((event_ring, memory))
+    }
+
+    fn wait_until(&self, stage: Stage, mut f: impl FnMut() -> bool) -> Result<(), Error> {
+        let start = self.clock.now();
+
+        while !f() {
+            if self.clock.now().saturating_sub(start) > self.timeout {
+                return Err(Error::Timeout(stage));
+            }
+
+            core::hint::spin_loop();
+        }
+
+        Ok(())
+    }
+
+    fn allocate_slice<T>(
+        &mut self,
+        len: usize,
+        requirement: Requirement,
+    ) -> Result<DmaBox<[T], A>, Error>
+    where
+        T: Default,
+    {
+        DmaBox::new_slice(len, self.allocator.clone(), requirement).ok_or(Error::AllocationFailed)
+    }
+}
+
+/// A running xHC initialized by [`Initializer`].
+///
+/// This struct owns the memory the xHC accesses. Halt the xHC before dropping this struct, because
+/// dropping it frees the memory.
+#[derive(Debug)]
+pub struct Controller<A>
+where
+    A: Allocator + Clone,
+{
+    allocator: A,
+    max_device_slots: u8,
+    page_size: usize,
+    context_size: usize,
+    dcbaa: Dcbaa<A>,
+    _scratchpad: Option<ScratchpadBufferArray<A>>,
+    command_ring: ProducerRing<command::Allowed, 1>,
+    _command_ring_memory: SegmentMemory<A>,
+    event_ring: EventRing<1>,
+    _event_ring_memory: EventRingMemory<A>,
+}
+impl<A> Controller<A>
+where
+    A: Allocator + Clone,
+{
+    /// Returns the Command Ring.
+    ///
+    /// Ring the Host Controller doorbell with [`Registers::ring_command`] after enqueueing Command
+    /// TRBs.
+    pub fn command_ring(&mut self) -> &mut ProducerRing<command::Allowed, 1> {
+        &mut self.command_ring
+    }
+
+    /// Returns the Event Ring of the primary interrupter.
+    ///
+    /// Write [`EventRing::erdp`] to the Event Ring Dequeue Pointer register of the primary
+    /// interrupter after handling events.
+    pub fn event_ring(&mut self) -> &mut EventRing<1> {
+        &mut self.event_ring
+    }
+
+    /// Returns the allocator.
+    pub fn allocator(&mut self) -> &mut A {
+        &mut self.allocator
+    }
+
+    /// Returns the value written to the Max Device Slots Enabled field.
+    #[must_use]
+    pub fn max_device_slots(&self) -> u8 {
+        self.max_device_slots
+    }
+
+    /// Returns the page size of the xHC in bytes.
+    #[must_use]
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// Returns the size of a Context data structure in bytes, which is either 32 or 64.
+    #[must_use]
+    pub fn context_size(&self) -> usize {
+        self.context_size
+    }
+
+    /// Returns the Device Context Base Address Array.
+    ///
+    /// Set the pointer to the Device Context of a Device Slot before issuing an Address Device
+    /// Command for it.
+    pub fn dcbaa(&mut self) -> &mut Dcbaa<A> {
+        &mut self.dcbaa
+    }
+}
+
+#[cfg(test)]
+impl Controller<crate::dma::Heap> {
+    /// Creates a controller backed by the heap without initializing an xHC, to test the modules
+    /// which issue commands through it.
+    pub(crate) fn new_for_test(context_size: usize) -> Self {
+        use crate::dma::Heap;
+        use crate::registers::operational::PageSizeRegister;
+
+        // 1 means 4 KiB.
+        let page_size = PageSizeRegister::from_raw(1);
+
+        let command_ring_memory =
+            DmaBox::new_slice(16, Heap, Requirement::COMMAND_RING_SEGMENT).unwrap();
+        // SAFETY: The memory is owned by the returned `Controller` together with the ring.
+        let command_ring = ProducerRing::new([unsafe {
+            Segment::new(
+                command_ring_memory.virt_addr(),
+                command_ring_memory.phys_addr(),
+                command_ring_memory.len(),
+            )
+        }]);
+        let (event_ring, event_ring_memory) = interrupter::new_event_ring(&Heap, 16).unwrap();
+
+        Self {
+            allocator: Heap,
+            max_device_slots: 8,
+            page_size: page_size.bytes(),
+            context_size,
+            dcbaa: Dcbaa::new(8, Heap, page_size).unwrap(),
+            _scratchpad: None,
+            command_ring,
+            _command_ring_memory: command_ring_memory,
+            event_ring,
+            _event_ring_memory: event_ring_memory,
+        }
+    }
+}
+
+/// An error returned by [`Initializer::initialize`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub enum Error {
+    /// The xHC did not respond within the timeout.
+    Timeout(Stage),
+    /// The allocator failed to allocate memory.
+    AllocationFailed,
+}
+
+/// A step of the initialization which waits for the xHC.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub enum Stage {
+    /// Waiting for the Controller Not Ready bit to be cleared.
+    ControllerNotReady,
+    /// Waiting for the HC Halted bit to be set.
+    Halt,
+    /// Waiting for the Host Controller Reset bit to be cleared.
+    Reset,
+    /// Waiting for the HC Halted bit to be cleared.
+    Run,
+}
+
+/// The memory of a ring segment.
+type SegmentMemory<A> = DmaBox<[[u32; 4]], A>;
@@ -0,0 +1,376 @@
+//! Memory which the xHC accesses.
+//!
+//! The xHC reads and writes the rings, the contexts, and the other data structures through DMA.
+//! Such memory must be physically contiguous, and the xHC must know the physical address of it.
+//! [`Allocator`] is the interface through which this crate obtains such memory, and [`DmaBox`]
+//! owns a value placed in it.
+//!
+//! Each data structure has its own alignment and boundary requirements, which are listed in Table
+//! 6-1 of the xHCI specification. [`Requirement`] describes them.
+
+use core::alloc::Layout;
+use core::convert::TryFrom;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::ptr::{self, NonNull};
+
+/// An allocator of the memory which the xHC accesses through DMA.
+///
+/// # Safety
+///
+/// A memory block returned by [`Allocator::allocate`] must be physically contiguous, must be
+/// accessible through the returned virtual address, and must be located at the returned physical
+/// address. It must meet the requested layout, and must not cross the requested boundary. The
+/// block must stay valid until it is passed to [`Allocator::deallocate`].
+pub unsafe trait Allocator {
+    /// Allocates a memory block which meets `layout`, and returns the virtual and the physical
+    /// addresses of it.
+    ///
+    /// If `boundary` is not 0, it is a power of two, and the block must not cross a
+    /// `boundary`-byte boundary.
+    ///
+    /// This method returns [`None`] if it fails to allocate a block.
+    fn allocate(&mut self, layout: Layout, boundary: usize) -> Option<(usize, u64)>;
+
+    /// Deallocates the memory block whose virtual address is `virt`.
+    ///
+    /// # Safety
+    ///
+    /// `virt` must be the virtual address of a block returned by [`Allocator::allocate`] of this
+    /// allocator, `layout` must be the layout passed to it, and the block must not be deallocated
+    /// yet. The xHC must not access the block anymore.
+    unsafe fn deallocate(&mut self, virt: usize, layout: Layout);
+}
+
+/// The alignment and boundary requirements of a data structure.
+///
+/// The associated constants and functions return the requirements listed in Table 6-1 of the xHCI
+/// specification. `page_size` is the page size of the xHC in bytes, which is calculated from the
+/// Page Size Register.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub struct Requirement {
+    align: usize,
+    boundary: usize,
+}
+impl Requirement {
+    /// The requirements of a Transfer Ring segment.
+    pub const TRANSFER_RING_SEGMENT: Self = Self::const_new(16, 0x10000);
+    /// The requirements of a Command Ring segment.
+    pub const COMMAND_RING_SEGMENT: Self = Self::const_new(64, 0x10000);
+    /// The requirements of an Event Ring segment.
+    pub const EVENT_RING_SEGMENT: Self = Self::const_new(64, 0x10000);
+    /// The requirements of an Event Ring Segment Table.
+    pub const EVENT_RING_SEGMENT_TABLE: Self = Self::const_new(64, 0);
+    /// The requirements of a Scratchpad Buffer Array.
+    ///
+    /// Table 6-1 limits the array to a page, but an array of up to 1023 entries does not fit in a
+    /// page of 4096 bytes. Therefore only the alignment is required.
+    pub const SCRATCHPAD_BUFFER_ARRAY: Self = Self::const_new(64, 0);
+
+    /// Creates a new requirement.
+    ///
+    /// If `boundary` is 0, the memory block may cross any boundary.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `align` is not a power of two, or if `boundary` is neither 0 nor a
+    /// power of two.
+    #[must_use]
+    pub fn new(align: usize, boundary: usize) -> Self {
+        assert!(
+            align.is_power_of_two(),
+            "The alignment must be a power of two."
+        );
+        assert!(
+            boundary == 0 || boundary.is_power_of_two(),
+            "The boundary must be 0 or a power of two."
+        );
+
+        Self { align, boundary }
+    }
+
+    /// Returns the requirements of a Device Context.
+    #[must_use]
+    pub fn device_context(page_size: usize) -> Self {
+        Self::new(64, page_size)
+    }
+
+    /// Returns the requirements of an Input Context.
+    #[must_use]
+    pub fn input_context(page_size: usize) -> Self {
+        Self::new(64, page_size)
+    }
+
+    /// Returns the requirements of a Stream Context Array.
+    #[must_use]
+    pub fn stream_context_array(page_size: usize) -> Self {
+        Self::new(16, page_size)
+    }
+
+    /// Returns the requirements of a Device Context Base Address Array.
+    #[must_use]
+    pub fn device_context_base_address_array(page_size: usize) -> Self {
+        Self::new(64, page_size)
+    }
+
+    /// Returns the requirements of a Scratchpad Buffer.
+    #[must_use]
+    pub fn scratchpad_buffer(page_size: usize) -> Self {
+        Self::new(page_size, page_size)
+    }
+
+    /// Returns the requirements of a Port Bandwidth Context.
+    #[must_use]
+    pub fn port_bandwidth_context(page_size: usize) -> Self {
+        Self::new(16, page_size)
+    }
+
+    /// Returns the required alignment in bytes.
+    #[must_use]
+    pub fn align(self) -> usize {
+        self.align
+    }
+
+    /// Returns the boundary the memory block must not cross, or 0 if there is no such boundary.
+    #[must_use]
+    pub fn boundary(self) -> usize {
+        self.boundary
+    }
+
+    const fn const_new(align: usize, boundary: usize) -> Self {
+        Self { align, boundary }
+    }
+
+    fn layout_of(self, layout: Layout) -> Layout {
+        let layout = layout.align_to(self.align).unwrap();
+
+        assert_ne!(layout.size(), 0, "The value must not be zero-sized.");
+        assert!(
+            self.boundary == 0 || layout.size() <= self.boundary,
+            "The value is larger than the boundary."
+        );
+
+        layout
+    }
+
+    fn is_met_by(self, phys: u64, size: usize) -> bool {
+        let boundary = u64::try_from(self.boundary).unwrap();
+        let end = phys + u64::try_from(size).unwrap() - 1;
+
+        phys.trailing_zeros() >= self.align.trailing_zeros()
+            && (boundary == 0 || phys / boundary == end / boundary)
+    }
+}
+
+/// A pointer type which owns a value placed in the memory allocated by [`Allocator`].
+///
+/// Dropping this box frees the memory. The xHC must not access it after that.
+pub struct DmaBox<T, A>
+where
+    T: ?Sized,
+    A: Allocator + Clone,
+{
+    virt: NonNull<T>,
+    phys: u64,
+    layout: Layout,
+    allocator: A,
+    _marker: PhantomData<T>,
+}
+impl<T, A> DmaBox<T, A>
+where
+    A: Allocator + Clone,
+{
+    /// Allocates memory which meets `requirement`, and moves `x` into it.
+    ///
+    /// This method returns [`None`] if the allocator fails to allocate memory.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `T` is zero-sized, if the size of `T` is larger than the boundary, or
+    /// if the allocator returns memory which does not meet `requirement`.
+    pub fn new(x: T, mut allocator: A, requirement: Requirement) -> Option<Self> {
+        let layout = requirement.layout_of(Layout::new::<T>());
+        let (virt, phys) = allocate(&mut allocator, layout, requirement)?;
+        let virt = virt as *mut T;
+
+        // SAFETY: `Allocator` ensures that the memory is accessible.
+        unsafe { ptr::write(virt, x) };
+
+        Some(Self {
+            virt: NonNull::new(virt).unwrap(),
+            phys,
+            layout,
+            allocator,
+            _marker: PhantomData,
+        })
+    }
+}
+impl<T, A> DmaBox<[T], A>
+where
+    T: Default,
+    A: Allocator + Clone,
+{
+    /// Allocates memory which meets `requirement` for `len` elements, and fills it with the
+    /// default value of `T`.
+    ///
+    /// This method returns [`None`] if the allocator fails to allocate memory.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the slice is zero-sized, if the size of the slice is larger than the
+    /// boundary, or if the allocator returns memory which does not meet `requirement`.
+    pub fn new_slice(len: usize, mut allocator: A, requirement: Requirement) -> Option<Self> {
+        let layout = requirement.layout_of(Layout::array::<T>(len).unwrap());
+        let (virt, phys) = allocate(&mut allocator, layout, requirement)?;
+        let virt = virt as *mut T;
+
+        for i in 0..len {
+            // SAFETY: `Allocator` ensures that the memory is accessible.
+            unsafe { ptr::write(virt.add(i), T::default()) };
+        }
+
+        Some(Self {
+            virt: NonNull::new(ptr::slice_from_raw_parts_mut(virt, len)).unwrap(),
+            phys,
+            layout,
+            allocator,
+            _marker: PhantomData,
+        })
+    }
+}
+impl<T, A> DmaBox<T, A>
+where
+    T: ?Sized,
+    A: Allocator + Clone,
+{
+    /// Returns the virtual address of the value.
+    #[must_use]
+    pub fn virt_addr(&self) -> usize {
+        self.virt.as_ptr().cast::<u8>() as usize
+    }
+
+    /// Returns the physical address of the value.
+    #[must_use]
+    pub fn phys_addr(&self) -> u64 {
+        self.phys
+    }
+}
+impl<T, A> Deref for DmaBox<T, A>
+where
+    T: ?Sized,
+    A: Allocator + Clone,
+{
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: The value is initialized in the constructors.
+        unsafe { self.virt.as_ref() }
+    }
+}
+impl<T, A> DerefMut for DmaBox<T, A>
+where
+    T: ?Sized,
+    A: Allocator + Clone,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY: The value is initialized in the constructors.
+        unsafe { self.virt.as_mut() }
+    }
+}
+impl<T, A> Drop for DmaBox<T, A>
+where
+    T: ?Sized,
+    A: Allocator + Clone,
+{
+    fn drop(&mut self) {
+        // SAFETY: The memory is allocated by `self.allocator` with `self.layout`.
+        unsafe {
+            ptr::drop_in_place(self.virt.as_ptr());
+            self.allocator.deallocate(self.virt_addr(), self.layout);
+        }
+    }
+}
+impl<T, A> fmt::Debug for DmaBox<T, A>
+where
+    T: ?Sized,
+    A: Allocator + Clone,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DmaBox")
+            .field("virt", &self.virt_addr())
+            .field("phys", &self.phys)
+            .finish_non_exhaustive()
+    }
+}
+
+/// An allocator which allocates memory from the global heap.
+///
+/// This allocator regards a virtual address as a physical address, so it is only useful if the
+/// memory is identity-mapped, or to test the logic of this crate without the xHC.
+#[cfg(any(test, feature = "alloc"))]
+#[derive(Copy, Clone, Default, Debug)]
+pub struct Heap;
+#[cfg(any(test, feature = "alloc"))]
+unsafe impl Allocator for Heap {
+    fn allocate(&mut self, layout: Layout, _boundary: usize) -> Option<(usize, u64)> {
+        // SAFETY: `Requirement` ensures that the layout is not zero-sized.
+        let virt = unsafe { alloc::alloc::alloc(Self::layout_of(layout)) } as usize;
+
+        (virt != 0).then_some((virt, virt as u64))
+    }
+
+    unsafe fn deallocate(&mut self, virt: usize, layout: Layout) {
+        alloc::alloc::dealloc(virt as *mut u8, Self::layout_of(layout));
+    }
+}
+#[cfg(any(test, feature = "alloc"))]
+impl Heap {
+    /// A block aligned to the power of two not less than its size never crosses a boundary which
+    /// is not less than its size.
+    fn layout_of(layout: Layout) -> Layout {
+        layout.align_to(layout.size().next_power_of_two()).unwrap()
+    }
+}
+
+fn allocate<A>(allocator: &mut A, layout: Layout, requirement: Requirement) -> Option<(usize, u64)>
+where
+    A: Allocator,
+{
+    let (virt, phys) = allocator.allocate(layout, requirement.boundary)?;
+
+    assert_eq!(
+        virt % layout.align(),
+        0,
+        "The allocator returned a misaligned memory block."
+    );
+    assert!(
+        requirement.is_met_by(phys, layout.size()),
+        "The allocator returned a memory block which does not meet the requirement."
+    );
+
+    Some((virt, phys))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn heap_meets_requirement() {
+        let b = DmaBox::new([0_u32; 4], Heap, Requirement::COMMAND_RING_SEGMENT).unwrap();
+        assert_eq!(b.phys_addr() % 64, 0);
+        assert_eq!(b.virt_addr(), b.as_ptr() as usize);
+
+        let s = DmaBox::<[u64], _>::new_slice(33, Heap, Requirement::new(64, 4096)).unwrap();
+        assert_eq!(s.len(), 33);
+        assert!(s.iter().all(|x| *x == 0));
+        assert_eq!(s.phys_addr() / 4096, (s.phys_addr() + 33 * 8 - 1) / 4096);
+    }
+
+    #[test]
+    #[should_panic(expected = "The value is larger than the boundary.")]
+    fn value_larger_than_boundary_is_rejected() {
+        let _ = DmaBox::<[u8], _>::new_slice(8192, Heap, Requirement::new(64, 4096));
+    }
+}
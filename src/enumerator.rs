@@ -0,0 +1,1268 @@
+//! Device enumeration.
+//!
+//! [`Enumerator`] performs the sequence described in section 4.3 of the xHCI specification for a
+//! device connected to a Root Hub Port: it resets the port, enables a Device Slot, addresses the
+//! device, reads its descriptors, and configures it with the first configuration.
+//!
+//! The enumerator does not access the registers. Each method returns an [`Action`] the caller
+//! performs, and the caller advances the enumerator with the events the xHC reports. Therefore
+//! multiple devices can be enumerated concurrently with a single Event Ring.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use core::alloc::Layout;
+//! # use xhci::controller::Controller;
+//! # use xhci::dma::Allocator;
+//! use xhci::enumerator::{Action, Enumerator};
+//! use xhci::registers::EndpointTarget;
+//! use xhci::ring::trb::event::Allowed;
+//! #
+//! # #[derive(Clone, Debug)]
+//! # struct DmaAllocator;
+//! # unsafe impl Allocator for DmaAllocator {
+//! #     fn allocate(&mut self, layout: Layout, boundary: usize) -> Option<(usize, u64)> {
+//! #         unimplemented!()
+//! #     }
+//! #
+//! #     unsafe fn deallocate(&mut self, virt: usize, layout: Layout) {
+//! #         unimplemented!()
+//! #     }
+//! # }
+//! #
+//! # #[derive(Clone)]
+//! # struct MemoryMapper;
+//! # impl xhci::accessor::Mapper for MemoryMapper {
+//! #     unsafe fn map(&mut self, phys_base: usize, bytes: usize) -> core::num::NonZeroUsize {
+//! #         unimplemented!()
+//! #     }
+//! #
+//! #     fn unmap(&mut self, virt_base: usize, bytes: usize) {
+//! #         unimplemented!()
+//! #     }
+//! # }
+//! #
+//! # let mut controller: Controller<DmaAllocator> = unimplemented!();
+//! # let mut r: xhci::Registers<MemoryMapper> = unimplemented!();
+//! # let port_id = 1;
+//!
+//! let mut enumerator = Enumerator::new(port_id, &mut controller).expect("Out of memory.");
+//! let mut action = enumerator.start();
+//!
+//! loop {
+//!     match action {
+//!         Action::ResetPort(port_id) => {
+//!             // Reset the port, e.g., with `xhci::root_hub::RootHub::reset_port`.
+//!         }
+//!         Action::RingCommandDoorbell => r.ring_command(),
+//!         Action::RingControlEndpointDoorbell(slot_id) => {
+//!             r.ring_endpoint(slot_id, EndpointTarget::new(1, 0));
+//!         }
+//!         Action::Done => break,
+//!     }
+//!
+//!     action = loop {
+//!         # let event: Allowed = unimplemented!();
+//!         // Wait for an event.
+//!         let next = match event {
+//!             Allowed::PortStatusChange(_) => {
+//!                 # let portsc = unimplemented!();
+//!                 // Read the PORTSC register and clear the Port Reset Change bit.
+//!                 Some(enumerator.on_port_reset(portsc, &mut controller))
+//!             }
+//!             Allowed::CommandCompletion(e) => enumerator
+//!                 .on_command_completion(&e, &mut controller)
+//!                 .transpose(),
+//!             Allowed::TransferEvent(e) => enumerator
+//!                 .on_transfer_event(&e, &mut controller)
+//!                 .transpose(),
+//!             _ => None,
+//!         };
+//!
+//!         if let Some(next) = next {
+//!             break next.expect("Failed to enumerate the device.");
+//!         }
+//!     };
+//! }
+//!
+//! let device = enumerator.into_device().unwrap();
+//! ```
+
+use crate::context::{
+    Device32Byte, Device64Byte, EndpointContextBuilder, EndpointContextError, Input32Byte,
+    Input64Byte, InputHandler, SlotContextBuilder,
+};
+use crate::controller::Controller;
+use crate::dma::{Allocator, DmaBox, Requirement};
+use crate::registers::operational::PortStatusAndControlRegister;
+use crate::ring::producer::ProducerRing;
+use crate::ring::trb::event::{CommandCompletion, CompletionCode, TransferEvent};
+use crate::ring::trb::{command, transfer};
+use crate::ring::Segment;
+use crate::usb::descriptor::{self, Descriptor, Descriptors};
+use crate::usb::request::ControlRequest;
+use crate::usb::Speed;
+use core::convert::{TryFrom, TryInto};
+use core::iter::Peekable;
+use core::sync::atomic::{self, Ordering};
+
+/// The number of TRBs of each Transfer Ring the enumerator allocates.
+const TRANSFER_RING_SIZE: usize = 256;
+
+/// The size of the buffer which receives descriptors.
+const BUFFER_SIZE: usize = 4096;
+
+/// A state machine which enumerates a device connected to a Root Hub Port.
+///
+/// See the [module-level documentation](self) for the usage.
+#[derive(Debug)]
+pub struct Enumerator<A>
+where
+    A: Allocator + Clone,
+{
+    port_id: u8,
+    slot_type: u8,
+    state: State,
+    speed: Option<Speed>,
+    slot_id: u8,
+    pending_command: Option<u64>,
+    input: InputContext<A>,
+    device: DeviceContext<A>,
+    rings: TransferRings<A>,
+    buffer: DmaBox<[u8], A>,
+    requested_length: u16,
+    residual_length: u32,
+    device_descriptor: Option<descriptor::Device>,
+    configuration: Option<descriptor::Configuration>,
+}
+impl<A> Enumerator<A>
+where
+    A: Allocator + Clone,
+{
+    /// Creates an enumerator for the device connected to the Root Hub Port `port_id`.
+    ///
+    /// This method allocates the Input Context, the Device Context, the Transfer Ring of the
+    /// Default Control Endpoint, and a buffer for descriptors.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`Error::AllocationFailed`] if the allocator fails to allocate memory.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `port_id == 0`.
+    pub fn new(port_id: u8, controller: &mut Controller<A>) -> Result<Self, Error> {
+        assert_ne!(port_id, 0, "The Port ID must not be 0.");
+
+        let page_size = controller.page_size();
+        let is_64byte = controller.context_size() == 64;
+        let a = controller.allocator().clone();
+
+        let input =
+            InputContext::new(is_64byte, a.clone(), page_size).ok_or(Error::AllocationFailed)?;
+        let device =
+            DeviceContext::new(is_64byte, a.clone(), page_size).ok_or(Error::AllocationFailed)?;
+        let buffer = DmaBox::new_slice(BUFFER_SIZE, a.clone(), Requirement::new(64, 0x10000))
+            .ok_or(Error::AllocationFailed)?;
+
+        let mut rings = TransferRings::new();
+        rings.allocate(1, a)?;
+
+        Ok(Self {
+            port_id,
+            slot_type: 0,
+            state: State::ResettingPort,
+            speed: None,
+            slot_id: 0,
+            pending_command: None,
+            input,
+            device,
+            rings,
+            buffer,
+            requested_length: 0,
+            residual_length: 0,
+            device_descriptor: None,
+            configuration: None,
+        })
+    }
+
+    /// Sets the Slot Type of the Enable Slot Command.
+    ///
+    /// The value must be the Protocol Slot Type of the port, which
+    /// [`RootHub::port`](crate::root_hub::RootHub::port) returns. It is 0 by default.
+    pub fn set_slot_type(&mut self, slot_type: u8) -> &mut Self {
+        self.slot_type = slot_type;
+        self
+    }
+
+    /// Sets the speed of the device.
+    ///
+    /// By default, [`Enumerator::on_port_reset`] converts the Port Speed field with
+    /// [`Speed::from_port_speed`], which assumes the default Protocol Speed IDs. If the xHCI
+    /// Supported Protocol Capability of the port defines Protocol Speed IDs, resolve the speed
+    /// with [`XhciSupportedProtocol::speed_info`] and set it with this method before calling
+    /// [`Enumerator::on_port_reset`].
+    ///
+    /// [`XhciSupportedProtocol::speed_info`]: crate::extended_capabilities::xhci_supported_protocol::XhciSupportedProtocol::speed_info
+    pub fn set_speed(&mut self, speed: Speed) -> &mut Self {
+        self.speed = Some(speed);
+        self
+    }
+
+    /// Returns the first action, which is to reset the port.
+    ///
+    /// After resetting the port, wait for the Port Status Change Event whose Port ID is the one of
+    /// the port, and call [`Enumerator::on_port_reset`].
+    #[must_use]
+    pub fn start(&self) -> Action {
+        Action::ResetPort(self.port_id)
+    }
+
+    /// Returns the current state.
+    #[must_use]
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Returns the Slot ID assigned to the device, or 0 if no Device Slot is enabled yet.
+    #[must_use]
+    pub fn slot_id(&self) -> u8 {
+        self.slot_id
+    }
+
+    /// Advances the enumerator with the PORTSC register of the port after its reset completes.
+    ///
+    /// This method enqueues an Enable Slot Command to the Command Ring.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the port is not enabled, if the speed is not set by
+    /// [`Enumerator::set_speed`] and the port speed is unknown, if the Command Ring is full, or if
+    /// the enumerator does not wait for a port reset.
+    pub fn on_port_reset(
+        &mut self,
+        portsc: PortStatusAndControlRegister,
+        controller: &mut Controller<A>,
+    ) -> Result<Action, Error> {
+        if self.state != State::ResettingPort {
+            return Err(Error::UnexpectedState(self.state));
+        }
+        if !portsc.port_enabled_disabled() {
+            return self.fail(Error::PortNotEnabled);
+        }
+
+        if self.speed.is_none() {
+            let port_speed = portsc.port_speed().protocol_speed_id_value();
+            match Speed::from_port_speed(port_speed) {
+                Some(s) => self.speed = Some(s),
+                None => return self.fail(Error::UnknownSpeed(port_speed)),
+            }
+        }
+
+        let mut enable_slot = command::EnableSlot::new();
+        enable_slot.set_slot_type(self.slot_type);
+
+        self.state = State::EnablingSlot;
+        self.issue_command(command::Allowed::EnableSlot(enable_slot), controller)
+    }
+
+    /// Advances the enumerator with a Command Completion Event.
+    ///
+    /// This method returns `Ok(None)` if the event is not for the command the enumerator issued.
+    /// Otherwise, it updates the Dequeue Pointer of the Command Ring.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the command failed, or if the next step fails.
+    pub fn on_command_completion(
+        &mut self,
+        event: &CommandCompletion,
+        controller: &mut Controller<A>,
+    ) -> Result<Option<Action>, Error> {
+        if self.pending_command != Some(event.command_trb_pointer()) {
+            return Ok(None);
+        }
+
+        self.pending_command = None;
+        controller
+            .command_ring()
+            .update_dequeue_pointer(event.command_trb_pointer());
+
+        let code = event.completion_code();
+        if code != Ok(CompletionCode::Success) {
+            return self.fail(Error::CommandFailed(code)).map(Some);
+        }
+
+        let action = match self.state {
+            State::EnablingSlot => {
+                self.slot_id = event.slot_id();
+                self.address_device(true, controller)
+            }
+            State::AddressingWithBsr => self.control_transfer(
+                ControlRequest::get_descriptor(descriptor::Type::Device, 0, 8),
+                State::ReadingMaxPacketSize,
+            ),
+            State::EvaluatingContext => self.address_device(false, controller),
+            State::Addressing => self.control_transfer(
+                ControlRequest::get_descriptor(
+                    descriptor::Type::Device,
+                    0,
+                    descriptor::Device::LENGTH.try_into().unwrap(),
+                ),
+                State::ReadingDeviceDescriptor,
+            ),
+            State::ConfiguringEndpoints => {
+                let value = self.configuration.unwrap().configuration_value();
+                self.control_transfer(
+                    ControlRequest::set_configuration(value),
+                    State::SettingConfiguration,
+                )
+            }
+            s => self.fail(Error::UnexpectedState(s)),
+        };
+
+        action.map(Some)
+    }
+
+    /// Advances the enumerator with a Transfer Event.
+    ///
+    /// This method returns `Ok(None)` if the event is not for the Default Control Endpoint of the
+    /// device, or if the event reports a short packet of the Data Stage and the Status Stage is
+    /// yet to complete. Otherwise, it updates the Dequeue Pointer of the Transfer Ring.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the transfer failed, if the device returned fewer bytes
+    /// than requested, if a descriptor is invalid, or if the next step fails.
+    pub fn on_transfer_event(
+        &mut self,
+        event: &TransferEvent,
+        controller: &mut Controller<A>,
+    ) -> Result<Option<Action>, Error> {
+        if self.slot_id == 0 || event.slot_id() != self.slot_id || event.endpoint_id() != 1 {
+            return Ok(None);
+        }
+
+        self.rings
+            .get(1)
+            .update_dequeue_pointer(event.trb_pointer());
+
+        let code = event.completion_code();
+        if code == Ok(CompletionCode::ShortPacket) {
+            // The Data Stage TRB has the Interrupt-on Short Packet bit set. The xHC continues to the
+            // Status Stage, whose completion is reported by another event.
+            self.residual_length = event.trb_transfer_length();
+            return Ok(None);
+        }
+        if code != Ok(CompletionCode::Success) {
+            return self.fail(Error::TransferFailed(code)).map(Some);
+        }
+
+        // Every request of the enumeration requires all of the requested bytes.
+        let residual = core::mem::take(&mut self.residual_length);
+        if residual > 0 {
+            let transferred = u32::from(self.requested_length).saturating_sub(residual);
+            return self
+                .fail(Error::ShortTransfer(transferred.try_into().unwrap()))
+                .map(Some);
+        }
+
+        atomic::fence(Ordering::Acquire);
+
+        let action = match self.state {
+            State::ReadingMaxPacketSize => self.update_max_packet_size(controller),
+            State::ReadingDeviceDescriptor => self.read_device_descriptor(),
+            State::ReadingConfigurationHeader => self.read_configuration_header(),
+            State::ReadingConfiguration => self.configure_endpoints(controller),
+            State::SettingConfiguration => {
+                self.state = State::Done;
+                Ok(Action::Done)
+            }
+            s => self.fail(Error::UnexpectedState(s)),
+        };
+
+        action.map(Some)
+    }
+
+    /// Returns the enumerated device if the enumeration has completed.
+    ///
+    /// This method returns [`None`] if the state is not [`State::Done`]. In that case, the memory
+    /// the enumerator owns is freed, so disable the Device Slot before calling this method.
+    #[must_use]
+    pub fn into_device(self) -> Option<EnumeratedDevice<A>> {
+        if self.state != State::Done {
+            return None;
+        }
+
+        Some(EnumeratedDevice {
+            port_id: self.port_id,
+            slot_id: self.slot_id,
+            speed: self.speed.unwrap(),
+            device_descriptor: self.device_descriptor.unwrap(),
+            configuration: self.configuration.unwrap(),
+            _input: self.input,
+            _device: self.device,
+            rings: self.rings,
+        })
+    }
+
+    fn address_device(
+        &mut self,
+        block_set_address_request: bool,
+        controller: &mut Controller<A>,
+    ) -> Result<Action, Error> {
+        if block_set_address_request {
+            self.init_input_context()?;
+            controller
+                .dcbaa()
+                .set_device_context(self.slot_id, self.device.phys_addr());
+        } else {
+            // The xHC restarts the Default Control Endpoint from the TR Dequeue Pointer of the
+            // Input Context, so it must point to the TRB after the consumed ones.
+            self.set_control_dequeue_pointer();
+            self.reset_add_context_flags(&[0, 1]);
+        }
+
+        let mut c = command::AddressDevice::new();
+        c.set_input_context_pointer(self.input.phys_addr())
+            .set_slot_id(self.slot_id);
+
+        if block_set_address_request {
+            c.set_block_set_address_request();
+            self.state = State::AddressingWithBsr;
+        } else {
+            self.state = State::Addressing;
+        }
+
+        self.issue_command(command::Allowed::AddressDevice(c), controller)
+    }
+
+    fn init_input_context(&mut self) -> Result<(), Error> {
+        let speed = self.speed.unwrap();
+
+        self.reset_add_context_flags(&[0, 1]);
+
+        let input = self.input.handler_mut();
+        SlotContextBuilder::new(self.port_id, speed)
+            .build(input.device_mut().slot_mut())
+            .expect("The TT fields are not set.");
+
+        EndpointContextBuilder::default_control_endpoint(speed)
+            .build(input.device_mut().endpoint_mut(1))
+            .map_err(Error::InvalidEndpoint)?;
+
+        self.set_control_dequeue_pointer();
+        Ok(())
+    }
+
+    /// Sets the TR Dequeue Pointer and the Dequeue Cycle State of the Default Control Endpoint to
+    /// the Enqueue Pointer and the Producer Cycle State of its Transfer Ring.
+    fn set_control_dequeue_pointer(&mut self) {
+        let ring = self.rings.get(1);
+        let (dequeue, cycle_state) = (ring.enqueue_pointer(), ring.cycle_state());
+
+        let ep0 = self.input.handler_mut().device_mut().endpoint_mut(1);
+        ep0.set_tr_dequeue_pointer(dequeue);
+        if cycle_state {
+            ep0.set_dequeue_cycle_state();
+        } else {
+            ep0.clear_dequeue_cycle_state();
+        }
+    }
+
+    fn update_max_packet_size(&mut self, controller: &mut Controller<A>) -> Result<Action, Error> {
+        // Only the first 8 bytes are read. The rest of the descriptor is not used here.
+        let mut raw = [0; descriptor::Device::LENGTH];
+        raw[..8].copy_from_slice(&self.buffer[..8]);
+        let device = match descriptor::Device::try_from(&raw[..]) {
+            Ok(d) => d,
+            Err(e) => return self.fail(Error::InvalidDescriptor(e)),
+        };
+
+        let speed = self.speed.unwrap();
+        let size = device
+            .max_packet_size_of_control_endpoint()
+            .filter(|&s| match speed {
+                Speed::Low => s == 8,
+                Speed::Full => matches!(s, 8 | 16 | 32 | 64),
+                Speed::High => s == 64,
+                Speed::SuperSpeed | Speed::SuperSpeedPlus => s == 512,
+            });
+        let Some(size) = size else {
+            return self.fail(Error::InvalidMaxPacketSize(device.max_packet_size0()));
+        };
+
+        let ep0 = self.input.handler_mut().device_mut().endpoint_mut(1);
+        if ep0.max_packet_size() == size {
+            return self.address_device(false, controller);
+        }
+
+        ep0.set_max_packet_size(size);
+        self.reset_add_context_flags(&[1]);
+
+        let mut c = command::EvaluateContext::new();
+        c.set_input_context_pointer(self.input.phys_addr())
+            .set_slot_id(self.slot_id);
+
+        self.state = State::EvaluatingContext;
+        self.issue_command(command::Allowed::EvaluateContext(c), controller)
+    }
+
+    fn read_device_descriptor(&mut self) -> Result<Action, Error> {
+        match descriptor::Device::try_from(&self.buffer[..]) {
+            Ok(d) => self.device_descriptor = Some(d),
+            Err(e) => return self.fail(Error::InvalidDescriptor(e)),
+        }
+
+        self.control_transfer(
+            ControlRequest::get_descriptor(
+                descriptor::Type::Configuration,
+                0,
+                descriptor::Configuration::LENGTH.try_into().unwrap(),
+            ),
+            State::ReadingConfigurationHeader,
+        )
+    }
+
+    fn read_configuration_header(&mut self) -> Result<Action, Error> {
+        let c = match descriptor::Configuration::try_from(&self.buffer[..]) {
+            Ok(c) => c,
+            Err(e) => return self.fail(Error::InvalidDescriptor(e)),
+        };
+
+        let total_length = c.total_length();
+        if usize::from(total_length) > BUFFER_SIZE {
+            return self.fail(Error::ConfigurationTooLarge(total_length));
+        }
+
+        self.configuration = Some(c);
+
+        self.control_transfer(
+            ControlRequest::get_descriptor(descriptor::Type::Configuration, 0, total_length),
+            State::ReadingConfiguration,
+        )
+    }
+
+    /// Sets up the Endpoint Contexts of the endpoints of the alternate setting 0 of each
+    /// interface, and issues a Configure Endpoint Command.
+    fn configure_endpoints(&mut self, controller: &mut Controller<A>) -> Result<Action, Error> {
+        let speed = self.speed.unwrap();
+        let total_length = self.configuration.unwrap().total_length();
+
+        let mut slot = SlotContextBuilder::new(self.port_id, speed);
+        self.reset_add_context_flags(&[0]);
+
+        let mut descriptors = Descriptors::new(&self.buffer[..total_length.into()]).peekable();
+        let mut alternate_setting = 0;
+
+        while let Some(d) = descriptors.next() {
+            let e = match d {
+                Ok(Descriptor::Interface(i)) => {
+                    alternate_setting = i.alternate_setting();
+                    continue;
+                }
+                Ok(Descriptor::Endpoint(e)) if alternate_setting == 0 => e,
+                Ok(_) => continue,
+                Err(e) => {
+                    self.state = State::Failed;
+                    return Err(Error::InvalidDescriptor(e));
+                }
+            };
+
+            let mut builder = EndpointContextBuilder::new(e, speed);
+            add_companions(&mut builder, &mut descriptors);
+
+            let dci = e.dci();
+            if let Err(e) = self.rings.allocate(dci, controller.allocator().clone()) {
+                self.state = State::Failed;
+                return Err(e);
+            }
+
+            let ring = self.rings.get(dci);
+            builder.set_tr_dequeue_pointer(ring.enqueue_pointer(), ring.cycle_state());
+
+            let input = self.input.handler_mut();
+            if let Err(e) = builder.build(input.device_mut().endpoint_mut(dci.into())) {
+                self.state = State::Failed;
+                return Err(Error::InvalidEndpoint(e));
+            }
+            input.control_mut().set_add_context_flag(dci.into());
+            slot.enable_endpoint(dci);
+        }
+
+        let input = self.input.handler_mut();
+        slot.build(input.device_mut().slot_mut())
+            .expect("The TT fields are not set.");
+        input
+            .control_mut()
+            .set_configuration_value(self.configuration.unwrap().configuration_value());
+
+        let mut c = command::ConfigureEndpoint::new();
+        c.set_input_context_pointer(self.input.phys_addr())
+            .set_slot_id(self.slot_id);
+
+        self.state = State::ConfiguringEndpoints;
+        self.issue_command(command::Allowed::ConfigureEndpoint(c), controller)
+    }
+
+    fn issue_command(
+        &mut self,
+        command: command::Allowed,
+        controller: &mut Controller<A>,
+    ) -> Result<Action, Error> {
+        match controller.command_ring().enqueue(command) {
+            Ok(a) => {
+                self.pending_command = Some(a);
+                Ok(Action::RingCommandDoorbell)
+            }
+            Err(_) => self.fail(Error::RingFull),
+        }
+    }
+
+    fn control_transfer(&mut self, request: ControlRequest, next: State) -> Result<Action, Error> {
+        let buffer = self.buffer.phys_addr();
+        let ring = self.rings.get(1);
+
+        for mut trb in request.transfer_trbs(buffer) {
+            if let transfer::Allowed::DataStage(d) = &mut trb {
+                d.set_interrupt_on_short_packet();
+            }
+            if ring.enqueue(trb).is_err() {
+                return self.fail(Error::RingFull);
+            }
+        }
+
+        self.requested_length = request.length();
+        self.residual_length = 0;
+        self.state = next;
+        Ok(Action::RingControlEndpointDoorbell(self.slot_id))
+    }
+
+    /// Clears all Add and Drop Context flags, and sets the Add Context flags of `indices`.
+    fn reset_add_context_flags(&mut self, indices: &[usize]) {
+        let control = self.input.handler_mut().control_mut();
+
+        for i in 0..32 {
+            if i >= 2 {
+                control.clear_drop_context_flag(i);
+            }
+            control.clear_add_context_flag(i);
+        }
+        for &i in indices {
+            control.set_add_context_flag(i);
+        }
+    }
+
+    fn fail<T>(&mut self, e: Error) -> Result<T, Error> {
+        self.state = State::Failed;
+        Err(e)
+    }
+}
+
+/// A device enumerated by [`Enumerator`].
+///
+/// This struct owns the Device Context and the Transfer Rings of the device. Disable the Device
+/// Slot before dropping this struct, because dropping it frees the memory.
+#[derive(Debug)]
+pub struct EnumeratedDevice<A>
+where
+    A: Allocator + Clone,
+{
+    port_id: u8,
+    slot_id: u8,
+    speed: Speed,
+    device_descriptor: descriptor::Device,
+    configuration: descriptor::Configuration,
+    _input: InputContext<A>,
+    _device: DeviceContext<A>,
+    rings: TransferRings<A>,
+}
+impl<A> EnumeratedDevice<A>
+where
+    A: Allocator + Clone,
+{
+    /// Returns the Root Hub Port the device is connected to.
+    #[must_use]
+    pub fn port_id(&self) -> u8 {
+        self.port_id
+    }
+
+    /// Returns the Slot ID of the device.
+    #[must_use]
+    pub fn slot_id(&self) -> u8 {
+        self.slot_id
+    }
+
+    /// Returns the speed of the device.
+    #[must_use]
+    pub fn speed(&self) -> Speed {
+        self.speed
+    }
+
+    /// Returns the Device descriptor.
+    #[must_use]
+    pub fn device_descriptor(&self) -> descriptor::Device {
+        self.device_descriptor
+    }
+
+    /// Returns the Configuration descriptor of the selected configuration.
+    #[must_use]
+    pub fn configuration(&self) -> descriptor::Configuration {
+        self.configuration
+    }
+
+    /// Returns the Transfer Ring of the endpoint of `dci`, if the endpoint is configured.
+    ///
+    /// The Default Control Endpoint has the DCI 1.
+    pub fn transfer_ring(&mut self, dci: u8) -> Option<&mut ProducerRing<transfer::Allowed, 1>> {
+        self.rings
+            .0
+            .get_mut(usize::from(dci).checked_sub(1)?)?
+            .as_mut()
+            .map(|r| &mut r.ring)
+    }
+}
+
+/// An action the caller of [`Enumerator`] performs.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub enum Action {
+    /// Reset the Root Hub Port of the Port ID, and call [`Enumerator::on_port_reset`] when the
+    /// Port Reset Change bit is set.
+    ResetPort(u8),
+    /// Ring the Host Controller doorbell, and pass the Command Completion Events to
+    /// [`Enumerator::on_command_completion`].
+    RingCommandDoorbell,
+    /// Ring the doorbell of the Default Control Endpoint of the Slot ID, and pass the Transfer
+    /// Events to [`Enumerator::on_transfer_event`].
+    RingControlEndpointDoorbell(u8),
+    /// The enumeration has completed. Call [`Enumerator::into_device`].
+    Done,
+}
+
+/// The state of [`Enumerator`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub enum State {
+    /// Waiting for the port reset to complete.
+    ResettingPort,
+    /// Waiting for the Enable Slot Command to complete.
+    EnablingSlot,
+    /// Waiting for the Address Device Command with the Block Set Address Request bit to complete.
+    AddressingWithBsr,
+    /// Reading the first 8 bytes of the Device descriptor to know the maximum packet size of the
+    /// Default Control Endpoint.
+    ReadingMaxPacketSize,
+    /// Waiting for the Evaluate Context Command which updates the maximum packet size to complete.
+    EvaluatingContext,
+    /// Waiting for the Address Device Command which sends a `SET_ADDRESS` request to complete.
+    Addressing,
+    /// Reading the whole Device descriptor.
+    ReadingDeviceDescriptor,
+    /// Reading the Configuration descriptor to know the total length of the configuration.
+    ReadingConfigurationHeader,
+    /// Reading the whole configuration.
+    ReadingConfiguration,
+    /// Waiting for the Configure Endpoint Command to complete.
+    ConfiguringEndpoints,
+    /// Waiting for the `SET_CONFIGURATION` request to complete.
+    SettingConfiguration,
+    /// The enumeration has completed.
+    Done,
+    /// The enumeration has failed. Disable the Device Slot if it is enabled.
+    Failed,
+}
+
+/// An error returned by [`Enumerator`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub enum Error {
+    /// The allocator failed to allocate memory.
+    AllocationFailed,
+    /// The port is not enabled after the reset.
+    PortNotEnabled,
+    /// The Port Speed field contains an unknown Protocol Speed ID.
+    UnknownSpeed(u8),
+    /// The Command Ring or the Transfer Ring is full.
+    RingFull,
+    /// A command did not complete successfully. The value is the Completion Code.
+    CommandFailed(Result<CompletionCode, u8>),
+    /// A control transfer did not complete successfully. The value is the Completion Code.
+    TransferFailed(Result<CompletionCode, u8>),
+    /// The device returned an invalid descriptor.
+    InvalidDescriptor(descriptor::Error),
+    /// The bMaxPacketSize0 field of the Device descriptor is invalid for the speed of the device.
+    /// The value is the field.
+    InvalidMaxPacketSize(u8),
+    /// The descriptors of an endpoint describe an invalid configuration.
+    InvalidEndpoint(EndpointContextError),
+    /// The device returned fewer bytes than requested. The value is the number of bytes
+    /// transferred.
+    ShortTransfer(u16),
+    /// The configuration is larger than the buffer of the enumerator.
+    ConfigurationTooLarge(u16),
+    /// The method is called in a state which does not expect it.
+    UnexpectedState(State),
+}
+
+fn add_companions<'a, I>(builder: &mut EndpointContextBuilder, descriptors: &mut Peekable<I>)
+where
+    I: Iterator<Item = Result<Descriptor<'a>, descriptor::Error>>,
+{
+    while let Some(Ok(d)) = descriptors.peek() {
+        match *d {
+            Descriptor::SuperSpeedEndpointCompanion(c) => {
+                builder.set_companion(c);
+            }
+            Descriptor::SuperSpeedPlusIsochronousEndpointCompanion(c) => {
+                builder.set_ssp_isochronous_companion(c);
+            }
+            _ => return,
+        }
+
+        descriptors.next();
+    }
+}
+
+#[derive(Debug)]
+enum InputContext<A>
+where
+    A: Allocator + Clone,
+{
+    Byte32(DmaBox<Input32Byte, A>),
+    Byte64(DmaBox<Input64Byte, A>),
+}
+impl<A> InputContext<A>
+where
+    A: Allocator + Clone,
+{
+    fn new(is_64byte: bool, allocator: A, page_size: usize) -> Option<Self> {
+        let r = Requirement::input_context(page_size);
+
+        Some(if is_64byte {
+            Self::Byte64(DmaBox::new(Input64Byte::new_64byte(), allocator, r)?)
+        } else {
+            Self::Byte32(DmaBox::new(Input32Byte::new_32byte(), allocator, r)?)
+        })
+    }
+
+    fn handler_mut(&mut self) -> &mut dyn InputHandler {
+        match self {
+            Self::Byte32(b) => &mut **b,
+            Self::Byte64(b) => &mut **b,
+        }
+    }
+
+    fn phys_addr(&self) -> u64 {
+        match self {
+            Self::Byte32(b) => b.phys_addr(),
+            Self::Byte64(b) => b.phys_addr(),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum DeviceContext<A>
+where
+    A: Allocator + Clone,
+{
+    Byte32(DmaBox<Device32Byte, A>),
+    Byte64(DmaBox<Device64Byte, A>),
+}
+impl<A> DeviceContext<A>
+where
+    A: Allocator + Clone,
+{
+    fn new(is_64byte: bool, allocator: A, page_size: usize) -> Option<Self> {
+        let r = Requirement::device_context(page_size);
+
+        Some(if is_64byte {
+            Self::Byte64(DmaBox::new(Device64Byte::new_64byte(), allocator, r)?)
+        } else {
+            Self::Byte32(DmaBox::new(Device32Byte::new_32byte(), allocator, r)?)
+        })
+    }
+
+    fn phys_addr(&self) -> u64 {
+        match self {
+            Self::Byte32(b) => b.phys_addr(),
+            Self::Byte64(b) => b.phys_addr(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TransferRing<A>
+where
+    A: Allocator + Clone,
+{
+    ring: ProducerRing<transfer::Allowed, 1>,
+    _memory: DmaBox<[[u32; 4]], A>,
+}
+
+/// The Transfer Rings indexed by DCI - 1.
+#[derive(Debug)]
+struct TransferRings<A>([Option<TransferRing<A>>; 31])
+where
+    A: Allocator + Clone;
+impl<A> TransferRings<A>
+where
+    A: Allocator + Clone,
+{
+    fn new() -> Self {
+        Self(core::array::from_fn(|_| None))
+    }
+
+    fn allocate(&mut self, dci: u8, allocator: A) -> Result<(), Error> {
+        let memory = DmaBox::new_slice(
+            TRANSFER_RING_SIZE,
+            allocator,
+            Requirement::TRANSFER_RING_SEGMENT,
+        )
+        .ok_or(Error::AllocationFailed)?;
+
+        // SAFETY: The memory is owned by the returned struct, and only the ring accesses it.
+        let segment =
+            unsafe { Segment::new(memory.virt_addr(), memory.phys_addr(), TRANSFER_RING_SIZE) };
+
+        self.0[usize::from(dci) - 1] = Some(TransferRing {
+            ring: ProducerRing::new([segment]),
+            _memory: memory,
+        });
+
+        Ok(())
+    }
+
+    fn get(&mut self, dci: u8) -> &mut ProducerRing<transfer::Allowed, 1> {
+        &mut self.0[usize::from(dci) - 1]
+            .as_mut()
+            .expect("The Transfer Ring is not allocated.")
+            .ring
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::context::{Endpoint32Byte, EndpointHandler};
+    use crate::dma::Heap;
+
+    #[rustfmt::skip]
+    const DEVICE: [u8; 18] = [
+        18, 1, 0x00, 0x02, 0, 0, 0, 64, 0x34, 0x12, 0x78, 0x56, 0, 1, 0, 0, 0, 1,
+    ];
+
+    #[rustfmt::skip]
+    const CONFIGURATION: [u8; 25] = [
+        9, 2, 25, 0, 1, 1, 0, 0x80, 50,
+        9, 4, 0, 0, 1, 0xff, 0, 0, 0,
+        7, 5, 0x81, 2, 64, 0, 0,
+    ];
+
+    fn portsc(speed: u32) -> PortStatusAndControlRegister {
+        PortStatusAndControlRegister::from_raw(0b11 | (speed << 10))
+    }
+
+    fn read_trb(addr: u64) -> [u32; 4] {
+        // SAFETY: `Heap` returns the virtual address as the physical one.
+        unsafe { core::ptr::read_volatile(addr as *const [u32; 4]) }
+    }
+
+    fn pending_command(e: &Enumerator<Heap>) -> command::Allowed {
+        command::Allowed::try_from(read_trb(e.pending_command.unwrap())).unwrap()
+    }
+
+    fn complete_command(
+        e: &mut Enumerator<Heap>,
+        c: &mut Controller<Heap>,
+        code: u8,
+    ) -> Result<Option<Action>, Error> {
+        let addr = e.pending_command.unwrap();
+        let raw = [
+            u32::try_from(addr & 0xffff_ffff).unwrap(),
+            u32::try_from(addr >> 32).unwrap(),
+            u32::from(code) << 24,
+            (33 << 10) | (1 << 24),
+        ];
+
+        e.on_command_completion(&CommandCompletion::try_from(raw).unwrap(), c)
+    }
+
+    /// Returns the Setup Stage TRB and the Data Stage TRB of the last control transfer.
+    fn last_control_transfer(
+        e: &mut Enumerator<Heap>,
+    ) -> (transfer::SetupStage, transfer::DataStage) {
+        let end = e.rings.get(1).enqueue_pointer();
+        let setup = transfer::Allowed::try_from(read_trb(end - 48)).unwrap();
+        let data = transfer::Allowed::try_from(read_trb(end - 32)).unwrap();
+
+        let (transfer::Allowed::SetupStage(setup), transfer::Allowed::DataStage(data)) =
+            (setup, data)
+        else {
+            panic!()
+        };
+        (setup, data)
+    }
+
+    /// Reports the completion of the TRB `back` TRBs before the Enqueue Pointer of the Default
+    /// Control Endpoint.
+    fn transfer_event(
+        e: &mut Enumerator<Heap>,
+        c: &mut Controller<Heap>,
+        (back, length, code): (u64, u32, u8),
+    ) -> Result<Option<Action>, Error> {
+        let pointer = e.rings.get(1).enqueue_pointer() - back * 16;
+        let raw = [
+            u32::try_from(pointer & 0xffff_ffff).unwrap(),
+            u32::try_from(pointer >> 32).unwrap(),
+            length | (u32::from(code) << 24),
+            (32 << 10) | (1 << 16) | (1 << 24),
+        ];
+
+        e.on_transfer_event(&TransferEvent::try_from(raw).unwrap(), c)
+    }
+
+    /// Writes `data` to the buffer and reports the completion of the Status Stage.
+    fn complete_transfer(
+        e: &mut Enumerator<Heap>,
+        c: &mut Controller<Heap>,
+        data: &[u8],
+    ) -> Result<Option<Action>, Error> {
+        e.buffer[..data.len()].copy_from_slice(data);
+        transfer_event(e, c, (1, 0, 1))
+    }
+
+    /// Drives the enumerator of a Full-speed device until it issues a `GET_DESCRIPTOR` request for the
+    /// first 8 bytes of the Device descriptor.
+    fn read_max_packet_size() -> (Enumerator<Heap>, Controller<Heap>) {
+        read_max_packet_size_at(1, None)
+    }
+
+    /// Same as [`read_max_packet_size`], but the Port Speed field is `port_speed`, and the speed is
+    /// set by [`Enumerator::set_speed`] if `speed` is not [`None`].
+    fn read_max_packet_size_at(
+        port_speed: u32,
+        speed: Option<Speed>,
+    ) -> (Enumerator<Heap>, Controller<Heap>) {
+        let mut c = Controller::new_for_test(32);
+        let mut e = Enumerator::new(1, &mut c).unwrap();
+        if let Some(s) = speed {
+            e.set_speed(s);
+        }
+
+        assert_eq!(e.start(), Action::ResetPort(1));
+        assert_eq!(
+            e.on_port_reset(portsc(port_speed), &mut c),
+            Ok(Action::RingCommandDoorbell)
+        );
+        assert!(matches!(
+            pending_command(&e),
+            command::Allowed::EnableSlot(_)
+        ));
+
+        assert_eq!(
+            complete_command(&mut e, &mut c, 1),
+            Ok(Some(Action::RingCommandDoorbell))
+        );
+        let command::Allowed::AddressDevice(a) = pending_command(&e) else {
+            panic!()
+        };
+        assert!(a.block_set_address_request());
+        assert_eq!(a.slot_id(), 1);
+        assert_eq!(c.dcbaa().device_context(1), e.device.phys_addr());
+
+        assert_eq!(
+            complete_command(&mut e, &mut c, 1),
+            Ok(Some(Action::RingControlEndpointDoorbell(1)))
+        );
+        let (setup, data) = last_control_transfer(&mut e);
+        assert_eq!(setup.length(), 8);
+        assert!(data.interrupt_on_short_packet());
+
+        (e, c)
+    }
+
+    #[test]
+    fn device_is_enumerated() {
+        let (mut e, mut c) = read_max_packet_size();
+
+        // The maximum packet size differs from the default one of Full-speed devices.
+        assert_eq!(
+            complete_transfer(&mut e, &mut c, &DEVICE[..8]),
+            Ok(Some(Action::RingCommandDoorbell))
+        );
+        assert!(matches!(
+            pending_command(&e),
+            command::Allowed::EvaluateContext(_)
+        ));
+        assert_eq!(
+            e.input
+                .handler_mut()
+                .device_mut()
+                .endpoint_mut(1)
+                .max_packet_size(),
+            64
+        );
+
+        assert_eq!(
+            complete_command(&mut e, &mut c, 1),
+            Ok(Some(Action::RingCommandDoorbell))
+        );
+        let command::Allowed::AddressDevice(a) = pending_command(&e) else {
+            panic!()
+        };
+        assert!(!a.block_set_address_request());
+
+        // The xHC must not run the consumed GET_DESCRIPTOR TD again.
+        let next = e.rings.get(1).enqueue_pointer();
+        let ep0 = e.input.handler_mut().device_mut().endpoint_mut(1);
+        assert_eq!(ep0.tr_dequeue_pointer() & !0xf, next);
+        assert!(ep0.dequeue_cycle_state());
+
+        assert_eq!(
+            complete_command(&mut e, &mut c, 1),
+            Ok(Some(Action::RingControlEndpointDoorbell(1)))
+        );
+        assert_eq!(last_control_transfer(&mut e).0.length(), 18);
+
+        assert_eq!(
+            complete_transfer(&mut e, &mut c, &DEVICE),
+            Ok(Some(Action::RingControlEndpointDoorbell(1)))
+        );
+        assert_eq!(last_control_transfer(&mut e).0.length(), 9);
+
+        assert_eq!(
+            complete_transfer(&mut e, &mut c, &CONFIGURATION[..9]),
+            Ok(Some(Action::RingControlEndpointDoorbell(1)))
+        );
+        assert_eq!(last_control_transfer(&mut e).0.length(), 25);
+
+        assert_eq!(
+            complete_transfer(&mut e, &mut c, &CONFIGURATION),
+            Ok(Some(Action::RingCommandDoorbell))
+        );
+        let command::Allowed::ConfigureEndpoint(_) = pending_command(&e) else {
+            panic!()
+        };
+        let control = e.input.handler_mut().control_mut();
+        assert!(control.add_context_flag(0));
+        assert!(control.add_context_flag(3));
+        assert!(!control.add_context_flag(1));
+
+        assert_eq!(
+            complete_command(&mut e, &mut c, 1),
+            Ok(Some(Action::RingControlEndpointDoorbell(1)))
+        );
+        assert_eq!(
+            transfer_event(&mut e, &mut c, (1, 0, 1)),
+            Ok(Some(Action::Done))
+        );
+
+        let mut device = e.into_device().unwrap();
+        assert_eq!(device.slot_id(), 1);
+        assert_eq!(device.speed(), Speed::Full);
+        assert_eq!(device.device_descriptor().vendor_id(), 0x1234);
+        assert!(device.transfer_ring(3).is_some());
+    }
+
+    #[test]
+    fn short_descriptor_fails() {
+        let (mut e, mut c) = read_max_packet_size();
+
+        // The Data Stage transferred 4 of 8 bytes.
+        assert_eq!(transfer_event(&mut e, &mut c, (2, 4, 13)), Ok(None));
+        assert_eq!(e.state(), State::ReadingMaxPacketSize);
+
+        assert_eq!(
+            complete_transfer(&mut e, &mut c, &DEVICE[..4]),
+            Err(Error::ShortTransfer(4))
+        );
+        assert_eq!(e.state(), State::Failed);
+    }
+
+    #[test]
+    fn invalid_max_packet_size_fails() {
+        for size in [0, 7, 33] {
+            let (mut e, mut c) = read_max_packet_size();
+            let mut d = DEVICE;
+            d[7] = size;
+
+            assert_eq!(
+                complete_transfer(&mut e, &mut c, &d[..8]),
+                Err(Error::InvalidMaxPacketSize(size))
+            );
+            assert_eq!(e.state(), State::Failed);
+        }
+
+        // A SuperSpeed device must report 2^9 bytes.
+        let (mut e, mut c) = read_max_packet_size_at(4, None);
+        let mut d = DEVICE;
+        d[2..4].copy_from_slice(&[0x00, 0x03]);
+        d[7] = 6;
+        assert_eq!(
+            complete_transfer(&mut e, &mut c, &d[..8]),
+            Err(Error::InvalidMaxPacketSize(6))
+        );
+    }
+
+    #[test]
+    fn speed_set_by_caller_is_used() {
+        // Port Speed 7 is a custom Protocol Speed ID of High-speed.
+        let (mut e, mut c) = read_max_packet_size_at(7, Some(Speed::High));
+
+        // High-speed devices start with the correct maximum packet size.
+        assert_eq!(
+            complete_transfer(&mut e, &mut c, &DEVICE[..8]),
+            Ok(Some(Action::RingCommandDoorbell))
+        );
+        let command::Allowed::AddressDevice(a) = pending_command(&e) else {
+            panic!()
+        };
+        assert!(!a.block_set_address_request());
+    }
+
+    #[test]
+    fn failed_command_fails() {
+        let mut c = Controller::new_for_test(64);
+        let mut e = Enumerator::new(1, &mut c).unwrap();
+
+        assert_eq!(
+            e.on_port_reset(portsc(0), &mut c),
+            Err(Error::UnknownSpeed(0))
+        );
+
+        let mut e = Enumerator::new(1, &mut c).unwrap();
+        e.on_port_reset(portsc(4), &mut c).unwrap();
+        assert_eq!(
+            complete_command(&mut e, &mut c, 9),
+            Err(Error::CommandFailed(Ok(
+                CompletionCode::NoSlotsAvailableError
+            )))
+        );
+        assert_eq!(e.state(), State::Failed);
+        assert!(e.into_device().is_none());
+    }
+
+    #[test]
+    fn companions_are_added_to_builder() {
+        #[rustfmt::skip]
+        let bytes = [
+            7, 5, 0x81, 1, 0, 4, 1,
+            6, 48, 0, 0x80, 0, 0,
+            8, 49, 0, 0, 0, 0, 0x10, 0,
+            7, 5, 0x02, 2, 0, 4, 0,
+        ];
+        let mut descriptors = Descriptors::new(&bytes).peekable();
+
+        let Some(Ok(Descriptor::Endpoint(e))) = descriptors.next() else {
+            panic!()
+        };
+        let mut builder = EndpointContextBuilder::new(e, Speed::SuperSpeedPlus);
+        add_companions(&mut builder, &mut descriptors);
+
+        let mut cx = Endpoint32Byte::default();
+        builder.build(&mut cx).unwrap();
+        assert_eq!(cx.max_endpoint_service_time_interval_payload_high(), 0x10);
+        assert!(matches!(
+            descriptors.next(),
+            Some(Ok(Descriptor::Endpoint(_)))
+        ));
+    }
+}
@@ -1,10 +1,12 @@
 //! Debug Capability.
 
 use super::ExtendedCapability;
+use crate::registers::operational::{PortLinkState, PortSpeed};
 use accessor::single;
 use accessor::Mapper;
 use bit_field::BitField;
 use core::convert::TryInto;
+use num_traits::FromPrimitive;
 
 /// The entry point to the Debug Capability.
 #[derive(Debug)]
@@ -131,6 +133,9 @@ impl_debug_from_methods! {
 }
 
 /// Debug Capability Event Ring Segment Table Base Address Register.
+///
+/// The table this register points to is an array of
+/// [`EventRingSegmentTableEntry`](crate::ring::event::EventRingSegmentTableEntry).
 #[repr(transparent)]
 #[derive(Copy, Clone)]
 pub struct EventRingSegmentTableBaseAddress(u64);
@@ -274,15 +279,17 @@ impl PortStatusAndControl {
     ro_bit!(4, port_reset, "Port Reset");
 
     /// Returns the value of the Port Link State field.
+    ///
+    /// This method returns [`None`] if the value means `Reserved`.
     #[must_use]
-    pub fn port_link_state(self) -> u8 {
-        self.0.get_bits(5..=8).try_into().unwrap()
+    pub fn port_link_state(self) -> Option<PortLinkState> {
+        FromPrimitive::from_u32(self.0.get_bits(5..=8))
     }
 
     /// Returns the value of the Port Speed field.
     #[must_use]
-    pub fn port_speed(self) -> u8 {
-        self.0.get_bits(10..=13).try_into().unwrap()
+    pub fn port_speed(self) -> PortSpeed {
+        PortSpeed(self.0.get_bits(10..=13).try_into().unwrap())
     }
 
     rw1c_bit!(17, connect_status_change, "Connect Status Change");
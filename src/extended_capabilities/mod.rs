@@ -1,6 +1,8 @@
 //! The xHCI Extended Capabilities
 //!
 //! The mutable reference of this struct implements `IntoIterator` and it iterates over the xHCI Extended Capabilities.
+//! The shared reference also implements `IntoIterator`, which yields only the IDs and the offsets of the capabilities.
+//! [`List::find_debug`], [`List::legacy_support`], [`List::local_memory`], and [`List::supported_protocols`] return the accessors to the specific capabilities.
 //!
 //! # Examples
 //!
@@ -82,6 +84,7 @@ pub struct List<M>
 where
     M: Mapper + Clone,
 {
+    mmio_base: usize,
     base: usize,
     m: M,
 }
@@ -139,9 +142,79 @@ where
             None
         } else {
             let base = mmio_base + (xecp << 2);
-            Some(Self { base, m: mapper })
+            Some(Self {
+                mmio_base,
+                base,
+                m: mapper,
+            })
         }
     }
+
+    /// Returns an iterator over the IDs and the offsets of the xHCI Extended Capabilities.
+    ///
+    /// Unlike [`IterMut`], this iterator reads only the header of each capability and does not
+    /// create accessors to the capabilities.
+    #[must_use]
+    pub fn iter(&self) -> Iter<M> {
+        Iter {
+            mmio_base: self.mmio_base,
+            current: Some(self.base),
+            m: self.m.clone(),
+        }
+    }
+
+    /// Returns the accessor to the Debug Capability.
+    ///
+    /// This method returns [`None`] if the xHC does not support the Debug Capability.
+    pub fn find_debug(&self) -> Option<Debug<M>> {
+        let e = self.find(Ty::UsbDebugCapability)?;
+        // SAFETY: `List::new` ensures that the all necessary conditions are fulfilled.
+        Some(unsafe { Debug::new(e.address(), &self.m) })
+    }
+
+    /// Returns the accessor to the USB Legacy Support Capability.
+    ///
+    /// This method returns [`None`] if the xHC does not support the USB Legacy Support
+    /// Capability.
+    pub fn legacy_support(&self) -> Option<UsbLegacySupport<M>> {
+        let e = self.find(Ty::UsbLegacySupport)?;
+        // SAFETY: `List::new` ensures that the all necessary conditions are fulfilled.
+        Some(unsafe { UsbLegacySupport::new(e.address(), self.m.clone()) })
+    }
+
+    /// Returns the accessor to the xHCI Local Memory Capability.
+    ///
+    /// This method returns [`None`] if the xHC does not support the xHCI Local Memory
+    /// Capability, or if its size is 0.
+    pub fn local_memory(&self) -> Option<XhciLocalMemory<M>> {
+        let e = self.find(Ty::LocalMemory)?;
+        // SAFETY: `List::new` ensures that the all necessary conditions are fulfilled.
+        unsafe { XhciLocalMemory::new(e.address(), self.m.clone()) }
+    }
+
+    /// Returns an iterator over the accessors to the xHCI Supported Protocol Capabilities.
+    pub fn supported_protocols(&self) -> impl Iterator<Item = XhciSupportedProtocol<M>> + '_ {
+        let m = self.m.clone();
+        self.iter()
+            .filter(|e| e.id == Ty::SupportedProtocol as u8)
+            // SAFETY: `List::new` ensures that the all necessary conditions are fulfilled.
+            .map(move |e| unsafe { XhciSupportedProtocol::new(e.address(), m.clone()) })
+    }
+
+    fn find(&self, ty: Ty) -> Option<Entry> {
+        self.iter().find(|e| e.id == ty as u8)
+    }
+}
+impl<M> IntoIterator for &List<M>
+where
+    M: Mapper + Clone,
+{
+    type Item = Entry;
+    type IntoIter = Iter<M>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
 }
 impl<M> IntoIterator for &mut List<M>
 where
@@ -155,24 +228,102 @@ where
     }
 }
 
-/// An iterator over the xHCI Extended Capability.
+/// An iterator over the IDs and the offsets of the xHCI Extended Capabilities.
+///
+/// [`List::iter`] returns this iterator.
 #[derive(Debug)]
-pub struct IterMut<M>
+pub struct Iter<M>
 where
     M: Mapper + Clone,
 {
+    mmio_base: usize,
     current: Option<usize>,
     m: M,
 }
+impl<M> Iter<M>
+where
+    M: Mapper + Clone,
+{
+    fn next_header(&mut self) -> Option<(usize, Header)> {
+        let current = self.current?;
+
+        // SAFETY: `List::iter` guarantees that `self.current` is the correct address.
+        let h: Header = unsafe { single::ReadWrite::new(current, self.m.clone()) }.read_volatile();
+
+        self.current = if h.next() == 0 {
+            None
+        } else {
+            Some(current + (usize::from(h.next()) << 2))
+        };
+
+        Some((current, h))
+    }
+}
+impl<M> Iterator for Iter<M>
+where
+    M: Mapper + Clone,
+{
+    type Item = Entry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (address, h) = self.next_header()?;
+
+        Some(Entry {
+            mmio_base: self.mmio_base,
+            offset: address - self.mmio_base,
+            id: h.id(),
+        })
+    }
+}
+
+/// The ID and the offset of an xHCI Extended Capability.
+///
+/// [`Iter`] yields values of this type.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub struct Entry {
+    mmio_base: usize,
+    offset: usize,
+    id: u8,
+}
+impl Entry {
+    /// Returns the Capability ID.
+    #[must_use]
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    /// Returns the offset of the capability from the MMIO base address in bytes.
+    #[must_use]
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns `true` if this crate supports the capability, i.e., if [`IterMut`] yields an
+    /// [`ExtendedCapability`] for it.
+    #[must_use]
+    pub fn is_supported(&self) -> bool {
+        Ty::from_u8(self.id).is_some()
+    }
+
+    fn address(&self) -> usize {
+        self.mmio_base + self.offset
+    }
+}
+
+/// An iterator over the xHCI Extended Capability.
+#[derive(Debug)]
+pub struct IterMut<M>
+where
+    M: Mapper + Clone,
+{
+    inner: Iter<M>,
+}
 impl<M> IterMut<M>
 where
     M: Mapper + Clone,
 {
     fn new(l: &List<M>) -> Self {
-        Self {
-            current: Some(l.base),
-            m: l.m.clone(),
-        }
+        Self { inner: l.iter() }
     }
 }
 impl<M> Iterator for IterMut<M>
@@ -182,18 +333,9 @@ where
     type Item = Result<ExtendedCapability<M>, NotSupportedId>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let current = self.current?;
-
-        // SAFETY: `Iter::new` guarantees that `self.current` is the correct address.
-        let h: Header = unsafe { single::ReadWrite::new(current, self.m.clone()) }.read_volatile();
-
-        self.current = if h.next() == 0 {
-            None
-        } else {
-            Some(current + (usize::from(h.next()) << 2))
-        };
+        let (current, h) = self.inner.next_header()?;
 
-        let c = unsafe { ExtendedCapability::new(current, h, self.m.clone()) };
+        let c = unsafe { ExtendedCapability::new(current, h, self.inner.m.clone()) };
         Some(c.ok_or_else(|| NotSupportedId(h.id())))
     }
 }
@@ -292,3 +434,64 @@ enum Ty {
     UsbDebugCapability = 10,
     ExtendedMessageInterrupt = 17,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::Identity;
+
+    #[test]
+    fn iter_yields_ids_and_offsets() {
+        // USB Legacy Support at 0x10, an unknown capability at 0x18, and Debug at 0x20.
+        let mut memory = [0_u32; 16];
+        memory[4] = 2 << 8 | 1;
+        memory[6] = 2 << 8 | 0xc0;
+        memory[8] = 10;
+        let mmio_base = memory.as_mut_ptr() as usize;
+        let l = List {
+            mmio_base,
+            base: mmio_base + 0x10,
+            m: Identity,
+        };
+
+        let mut it = l.iter();
+        let e = it.next().unwrap();
+        assert_eq!((e.id(), e.offset(), e.is_supported()), (1, 0x10, true));
+        let e = it.next().unwrap();
+        assert_eq!((e.id(), e.offset(), e.is_supported()), (0xc0, 0x18, false));
+        let e = it.next().unwrap();
+        assert_eq!((e.id(), e.offset(), e.is_supported()), (10, 0x20, true));
+        assert!(it.next().is_none());
+    }
+
+    #[test]
+    fn finders_return_typed_accessors() {
+        // USB Legacy Support at 0x10, and xHCI Supported Protocols of USB 3.1 at 0x18 and of
+        // USB 2.0 at 0x28.
+        let mut memory = [0_u32; 16];
+        memory[4] = 1 << 16 | 2 << 8 | 1;
+        memory[6] = 3 << 24 | 1 << 16 | 4 << 8 | 2;
+        memory[8] = 2 << 8 | 1;
+        memory[10] = 2 << 24 | 2;
+        memory[12] = 2 << 8 | 3;
+        let mmio_base = memory.as_mut_ptr() as usize;
+        let l = List {
+            mmio_base,
+            base: mmio_base + 0x10,
+            m: Identity,
+        };
+
+        let legacy = l.legacy_support().unwrap();
+        assert!(legacy.usblegsup.read_volatile().hc_bios_owned_semaphore());
+        assert!(l.find_debug().is_none());
+        assert!(l.local_memory().is_none());
+
+        assert!(l
+            .supported_protocols()
+            .map(|p| {
+                let h = p.header.read_volatile();
+                (h.major_revision(), h.compatible_port_offset())
+            })
+            .eq([(3, 1), (2, 3)]));
+    }
+}
@@ -1,7 +1,9 @@
 //! USB Legacy Support Capability
 
 use super::ExtendedCapability;
+use crate::controller::Clock;
 use accessor::{single, Mapper};
+use core::time::Duration;
 
 /// USB Legacy Support Capability.
 #[derive(Debug)]
@@ -31,13 +33,67 @@ where
     /// This method panics if `base` is not aligned correctly.
     pub unsafe fn new(base: usize, m: M) -> Self {
         let usblegsup = single::ReadWrite::new(base, m.clone());
-        let usblegctlsts = single::ReadWrite::new(base, m);
+        let usblegctlsts = single::ReadWrite::new(base + 4, m);
 
         Self {
             usblegsup,
             usblegctlsts,
         }
     }
+
+    /// Requests the ownership of the xHC from the BIOS, and disables the SMIs of the xHC.
+    ///
+    /// This method sets the HC OS Owned Semaphore and waits until the BIOS clears the HC BIOS
+    /// Owned Semaphore. If the BIOS does not release the xHC within `timeout`, which is measured
+    /// with `clock`, this method clears the HC BIOS Owned Semaphore by itself. Then it disables all
+    /// SMI sources and clears the SMI status bits.
+    ///
+    /// Call this method before resetting the xHC.
+    pub fn request_os_ownership<C>(&mut self, clock: &C, timeout: Duration) -> Handoff
+    where
+        C: Clock,
+    {
+        let bios_owned = self.usblegsup.read_volatile().hc_bios_owned_semaphore();
+
+        self.usblegsup.update_volatile(|l| {
+            l.set_hc_os_owned_semaphore();
+        });
+
+        let start = clock.now();
+        let forced = loop {
+            if !self.usblegsup.read_volatile().hc_bios_owned_semaphore() {
+                break false;
+            }
+            if clock.now().saturating_sub(start) > timeout {
+                self.usblegsup.update_volatile(|l| {
+                    l.clear_hc_bios_owned_semaphore();
+                });
+                break true;
+            }
+
+            core::hint::spin_loop();
+        };
+        let elapsed = clock.now().saturating_sub(start);
+
+        let control_status = self.usblegctlsts.read_volatile();
+        self.usblegctlsts.update_volatile(|c| {
+            c.clear_usb_smi_enable()
+                .clear_smi_on_host_system_error_enable()
+                .clear_smi_on_os_ownership_enable()
+                .clear_smi_on_pci_command_enable()
+                .clear_smi_on_bar_enable()
+                .clear_smi_on_os_ownership_change()
+                .clear_smi_on_pci_command()
+                .clear_smi_on_bar();
+        });
+
+        Handoff {
+            bios_owned,
+            forced,
+            elapsed,
+            control_status,
+        }
+    }
 }
 impl<M> From<UsbLegacySupport<M>> for ExtendedCapability<M>
 where
@@ -48,6 +104,43 @@ where
     }
 }
 
+/// The result of [`UsbLegacySupport::request_os_ownership`].
+#[derive(Copy, Clone, Debug)]
+pub struct Handoff {
+    bios_owned: bool,
+    forced: bool,
+    elapsed: Duration,
+    control_status: UsbLegacySupportControlStatus,
+}
+impl Handoff {
+    /// Returns `true` if the BIOS owned the xHC when the ownership was requested.
+    #[must_use]
+    pub fn bios_owned(&self) -> bool {
+        self.bios_owned
+    }
+
+    /// Returns `true` if the BIOS did not release the xHC within the timeout and the HC BIOS Owned
+    /// Semaphore was cleared forcibly.
+    #[must_use]
+    pub fn forced(&self) -> bool {
+        self.forced
+    }
+
+    /// Returns the time spent waiting for the BIOS.
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    /// Returns the USB Legacy Support Control/Status register before the SMIs were disabled.
+    ///
+    /// The value tells which SMI sources the BIOS enabled and which SMIs were pending.
+    #[must_use]
+    pub fn control_status(&self) -> UsbLegacySupportControlStatus {
+        self.control_status
+    }
+}
+
 /// The first 4-byte of the USB Legacy Support Capability.
 #[repr(transparent)]
 #[derive(Copy, Clone)]
@@ -97,3 +190,39 @@ impl_debug_from_methods! {
         smi_on_bar,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::Identity;
+    use core::cell::Cell;
+
+    struct Ticks(Cell<u64>);
+    impl Clock for Ticks {
+        fn now(&self) -> Duration {
+            self.0.set(self.0.get() + 1);
+            Duration::from_millis(self.0.get())
+        }
+    }
+
+    #[test]
+    fn bios_ownership_is_cleared_on_timeout() {
+        // The BIOS owns the xHC, enables the USB SMI, and has a pending SMI on BAR.
+        let mut memory = [1 << 16, 1 | 1 << 31];
+        let base = memory.as_mut_ptr() as usize;
+        let mut u = unsafe { UsbLegacySupport::new(base, Identity) };
+
+        let h = u.request_os_ownership(&Ticks(Cell::new(0)), Duration::from_millis(10));
+
+        assert!(h.bios_owned());
+        assert!(h.forced());
+        assert!(h.elapsed() > Duration::from_millis(10));
+        assert!(h.control_status().usb_smi_enable());
+        assert!(h.control_status().smi_on_bar());
+
+        let legsup = u.usblegsup.read_volatile();
+        assert!(legsup.hc_os_owned_semaphore());
+        assert!(!legsup.hc_bios_owned_semaphore());
+        assert!(!u.usblegctlsts.read_volatile().usb_smi_enable());
+    }
+}
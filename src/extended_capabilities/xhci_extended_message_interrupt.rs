@@ -1,10 +1,16 @@
 //! xHCI Extended Message Interrupt Capability.
+//!
+//! This capability works like the MSI-X Capability of PCI, but all MSI-X Table entries share the
+//! upper 32 bits of their Message Addresses, which the Message Upper Address field holds. The
+//! MSI-X Table and the Pending Bit Array are accessed through [`Msix`].
 
 use super::ExtendedCapability;
+use accessor::array;
 use accessor::single;
 use accessor::Mapper;
 use bit_field::BitField;
-use core::convert::TryInto;
+use core::convert::{TryFrom, TryInto};
+use core::mem::size_of;
 
 /// xHCI Extended Message Interrupt Capability.
 #[repr(C)]
@@ -33,9 +39,12 @@ where
 #[derive(Copy, Clone)]
 pub struct MessageControl(u16);
 impl MessageControl {
+    rw_bit!(14, function_mask, "Function Mask");
     rw_bit!(15, msi_x_enable, "MSI-X Enable");
 
     /// Returns the value of the Table Size field.
+    ///
+    /// The number of the MSI-X Table entries is this value plus 1.
     #[must_use]
     pub fn table_size(self) -> u16 {
         self.0.get_bits(0..=10)
@@ -43,6 +52,7 @@ impl MessageControl {
 }
 impl_debug_from_methods! {
     MessageControl {
+        function_mask,
         msi_x_enable,
         table_size,
     }
@@ -71,3 +81,275 @@ impl_debug_from_methods! {
         bir,
     }
 }
+
+/// The MSI-X Table and the Pending Bit Array.
+///
+/// Interrupter `n` of the xHC signals its interrupts with the MSI-X Table entry `n`.
+#[derive(Debug)]
+pub struct Msix<M>
+where
+    M: Mapper + Clone,
+{
+    /// MSI-X Table.
+    pub table: array::ReadWrite<MsixTableEntry, M>,
+    /// Pending Bit Array.
+    ///
+    /// The bit `n % 64` of the element `n / 64` is set if the entry `n` has a pending message.
+    pub pba: array::ReadOnly<u64, M>,
+    upper_address: u32,
+}
+impl<M> Msix<M>
+where
+    M: Mapper + Clone,
+{
+    /// Creates accessors to the MSI-X Table and the Pending Bit Array which `capability` points
+    /// to.
+    ///
+    /// The capability has no field for the Pending Bit Array. Following section 7.3 of the xHCI
+    /// specification, the array is located right after the MSI-X Table, in the same BAR.
+    ///
+    /// `bar_base` receives a BAR Indicator Register (BIR) value and must return the base address of
+    /// the corresponding Base Address Register of the xHC.
+    ///
+    /// # Safety
+    ///
+    /// `bar_base` must return the correct base addresses, and the caller must ensure that the MSI-X
+    /// Table and the Pending Bit Array are accessed only through the returned accessor.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the MSI-X Table or the Pending Bit Array is not aligned correctly.
+    pub unsafe fn new<F>(capability: XhciExtendedMessageInterrupt, bar_base: F, mapper: M) -> Self
+    where
+        F: Fn(u8) -> usize,
+    {
+        let len = usize::from(capability.control.table_size()) + 1;
+        let o = capability.table_offset;
+        let table_base = bar_base(o.bir()) + usize::try_from(o.offset()).unwrap();
+        let pba_base = table_base + len * size_of::<MsixTableEntry>();
+
+        let table = array::ReadWrite::new(table_base, len, mapper.clone());
+        let pba = array::ReadOnly::new(pba_base, len.div_ceil(64), mapper);
+
+        Self {
+            table,
+            pba,
+            upper_address: capability.upper_address,
+        }
+    }
+
+    /// Returns the Message Address of the entry for the interrupter `interrupter`.
+    ///
+    /// The upper 32 bits are the Message Upper Address of the capability, and the lower 32 bits
+    /// are the Message Lower Address of the entry.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `interrupter` is out of the MSI-X Table.
+    #[must_use]
+    pub fn message_address(&self, interrupter: usize) -> u64 {
+        let lower = self
+            .table
+            .read_volatile_at(interrupter)
+            .message_lower_address();
+
+        u64::from(self.upper_address) << 32 | u64::from(lower)
+    }
+
+    /// Sets the Message Address and the Message Data of the entry for the interrupter
+    /// `interrupter`.
+    ///
+    /// The Mask bit of the entry is preserved. Mask the entry while it is being programmed if the
+    /// interrupter is enabled.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `interrupter` is out of the MSI-X Table, or if the upper 32 bits of
+    /// the Message Address of `message` differ from the Message Upper Address of the capability.
+    pub fn set_vector(&mut self, interrupter: usize, message: Message) {
+        let address = message.address();
+        assert_eq!(
+            address >> 32,
+            u64::from(self.upper_address),
+            "The upper 32 bits of the Message Address must be the Message Upper Address."
+        );
+
+        self.table.update_volatile_at(interrupter, |e| {
+            e.set_message_lower_address(address.get_bits(0..32).try_into().unwrap())
+                .set_message_data(message.data());
+        });
+    }
+
+    /// Masks the entry for the interrupter `interrupter`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `interrupter` is out of the MSI-X Table.
+    pub fn mask(&mut self, interrupter: usize) {
+        self.table.update_volatile_at(interrupter, |e| {
+            e.set_mask();
+        });
+    }
+
+    /// Unmasks the entry for the interrupter `interrupter`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `interrupter` is out of the MSI-X Table.
+    pub fn unmask(&mut self, interrupter: usize) {
+        self.table.update_volatile_at(interrupter, |e| {
+            e.clear_mask();
+        });
+    }
+
+    /// Returns `true` if the entry for the interrupter `interrupter` has a pending message.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `interrupter` is out of the MSI-X Table.
+    #[must_use]
+    pub fn is_pending(&self, interrupter: usize) -> bool {
+        assert!(
+            interrupter < self.table.len(),
+            "The interrupter must be within the MSI-X Table."
+        );
+
+        self.pba
+            .read_volatile_at(interrupter / 64)
+            .get_bit(interrupter % 64)
+    }
+}
+
+/// An entry of the MSI-X Table.
+///
+/// The upper 32 bits of the Message Address are the Message Upper Address of
+/// [`XhciExtendedMessageInterrupt`]. Use [`Msix::message_address`] to get the whole address.
+#[repr(transparent)]
+#[derive(Copy, Clone, Default)]
+pub struct MsixTableEntry([u32; 4]);
+impl MsixTableEntry {
+    /// Returns the value of the Message Lower Address field.
+    #[must_use]
+    pub fn message_lower_address(self) -> u32 {
+        self.0[0]
+    }
+
+    /// Sets the value of the Message Lower Address field.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `address` is not 4-byte aligned.
+    pub fn set_message_lower_address(&mut self, address: u32) -> &mut Self {
+        assert!(
+            address.trailing_zeros() >= 2,
+            "The Message Address must be 4-byte aligned."
+        );
+
+        self.0[0] = address;
+        self
+    }
+
+    rw_field!([2](0..=31), message_data, "Message Data", u32);
+    rw_bit!([3](0), mask, "Mask");
+}
+impl_debug_from_methods! {
+    MsixTableEntry {
+        message_lower_address,
+        message_data,
+        mask,
+    }
+}
+
+/// The message an MSI-X Table entry sends.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub struct Message {
+    address: u64,
+    data: u32,
+}
+impl Message {
+    /// Creates a message which writes `data` to `address`.
+    ///
+    /// The values depend on the platform. For example, on x86, `address` encodes the destination
+    /// processor and `data` encodes the interrupt vector.
+    #[must_use]
+    pub fn new(address: u64, data: u32) -> Self {
+        Self { address, data }
+    }
+
+    /// Returns the Message Address.
+    #[must_use]
+    pub fn address(self) -> u64 {
+        self.address
+    }
+
+    /// Returns the Message Data.
+    #[must_use]
+    pub fn data(self) -> u32 {
+        self.data
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::Identity;
+
+    #[test]
+    fn msix_table_entry_is_encoded() {
+        let mut e = MsixTableEntry::default();
+        e.set_message_lower_address(0xfee0_0000)
+            .set_message_data(0x41)
+            .set_mask();
+
+        assert_eq!(e.0, [0xfee0_0000, 0, 0x41, 1]);
+        assert_eq!(e.message_lower_address(), 0xfee0_0000);
+        assert!(e.mask());
+    }
+
+    #[test]
+    #[should_panic(expected = "The Message Address must be 4-byte aligned.")]
+    fn unaligned_message_address_is_rejected() {
+        MsixTableEntry::default().set_message_lower_address(0xfee0_0002);
+    }
+
+    #[test]
+    fn address_is_derived_from_upper_address() {
+        // Two entries at the offset 0x10 of the BAR, followed by the Pending Bit Array.
+        let mut bar = [0_u64; 8];
+        bar[6] = 0b10;
+        let capability = XhciExtendedMessageInterrupt {
+            _id: 17,
+            _next: 0,
+            control: MessageControl(1),
+            upper_address: 1,
+            table_offset: TableOffset(0x10),
+        };
+        let base = bar.as_mut_ptr() as usize;
+        let mut m = unsafe { Msix::new(capability, |_| base, Identity) };
+
+        m.set_vector(1, Message::new(0x1_fee0_0000, 0x41));
+        assert_eq!(m.message_address(1), 0x1_fee0_0000);
+        assert_eq!(m.table.read_volatile_at(1).message_data(), 0x41);
+        assert!(m.is_pending(1));
+        assert!(!m.is_pending(0));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "The upper 32 bits of the Message Address must be the Message Upper Address."
+    )]
+    fn different_upper_address_is_rejected() {
+        let mut bar = [0_u64; 8];
+        let capability = XhciExtendedMessageInterrupt {
+            _id: 17,
+            _next: 0,
+            control: MessageControl(1),
+            upper_address: 0,
+            table_offset: TableOffset(0),
+        };
+        let base = bar.as_mut_ptr() as usize;
+        let mut m = unsafe { Msix::new(capability, |_| base, Identity) };
+
+        m.set_vector(0, Message::new(0x1_fee0_0000, 0x41));
+    }
+}
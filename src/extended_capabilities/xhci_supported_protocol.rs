@@ -45,6 +45,37 @@ where
 
         Self { header, psis }
     }
+
+    /// Looks up the Protocol Speed ID whose Protocol Speed ID Value is `psiv`.
+    ///
+    /// If the PSI table contains an asymmetric pair for the value, the Rx entry, which precedes
+    /// the Tx one, is returned.
+    ///
+    /// This method returns [`None`] if the PSI table does not exist (i.e., `PSIC == 0`) or if no
+    /// entry has the value.
+    #[must_use]
+    pub fn protocol_speed_id(&self, psiv: u8) -> Option<ProtocolSpeedId> {
+        let table = self.psis.as_ref()?;
+        (0..table.len())
+            .map(|i| table.read_volatile_at(i))
+            .find(|id| id.protocol_speed_id_value() == psiv)
+    }
+
+    /// Returns the speed whose Protocol Speed ID Value is `psiv`.
+    ///
+    /// If the PSI table does not exist, the default Protocol Speed ID Values of the major revision
+    /// of the protocol are used. See [`SpeedInfo::from_default_id`].
+    #[must_use]
+    pub fn speed_info(&self, psiv: u8) -> Option<SpeedInfo> {
+        let major_revision = self.header.read_volatile().major_revision();
+
+        if self.psis.is_some() {
+            self.protocol_speed_id(psiv)
+                .map(|psi| SpeedInfo::from_protocol_speed_id(major_revision, psi))
+        } else {
+            SpeedInfo::from_default_id(major_revision, psiv)
+        }
+    }
 }
 impl<M> From<XhciSupportedProtocol<M>> for ExtendedCapability<M>
 where
@@ -78,6 +109,12 @@ impl Header {
         self.0[1]
     }
 
+    /// Returns the protocol which the Name String field identifies.
+    #[must_use]
+    pub fn protocol(self) -> Protocol {
+        Protocol::from(self.name_string())
+    }
+
     /// Returns the value of the Compatible Port Offset field.
     #[must_use]
     pub fn compatible_port_offset(self) -> u8 {
@@ -143,6 +180,9 @@ impl Header {
     }
 
     /// Returns the value of the Protocol Slot Type field.
+    ///
+    /// This value must be set to the Slot Type field of the Enable Slot Command TRB issued for a
+    /// device connected to the compatible ports.
     #[must_use]
     pub fn protocol_slot_type(self) -> u8 {
         self.0[3].get_bits(0..=4).try_into().unwrap()
@@ -153,6 +193,7 @@ impl_debug_from_methods! {
         minor_revision,
         major_revision,
         name_string,
+        protocol,
         compatible_port_offset,
         compatible_port_count,
         link_soft_error_count_capability,
@@ -221,6 +262,121 @@ impl_debug_from_methods! {
     }
 }
 
+/// The protocol identified by the Name String field.
+///
+/// [`Header::protocol`] returns a value of this type.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Protocol {
+    /// USB (`"USB "`).
+    Usb,
+    /// A protocol defined by a vendor.
+    ///
+    /// The value is the four ASCII characters of the Name String field.
+    Vendor([u8; 4]),
+}
+impl From<u32> for Protocol {
+    fn from(name_string: u32) -> Self {
+        match &name_string.to_le_bytes() {
+            b"USB " => Self::Usb,
+            name => Self::Vendor(*name),
+        }
+    }
+}
+impl From<Protocol> for u32 {
+    fn from(p: Protocol) -> Self {
+        match p {
+            Protocol::Usb => u32::from_le_bytes(*b"USB "),
+            Protocol::Vendor(name) => u32::from_le_bytes(name),
+        }
+    }
+}
+
+/// The speed described by a Protocol Speed ID.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct SpeedInfo {
+    bits_per_second: u64,
+    psi_type: PsiType,
+    full_duplex: bool,
+    link_protocol: Option<LinkProtocol>,
+}
+impl SpeedInfo {
+    /// Returns the speed of the default Protocol Speed ID Value `psiv` of a protocol whose major
+    /// revision is `major_revision`.
+    ///
+    /// The default values apply if the xHCI Supported Protocol Capability does not define any
+    /// Protocol Speed IDs (i.e., `PSIC == 0`). They are 1 (Full-speed), 2 (Low-speed), and 3
+    /// (High-speed) for USB2, and 4 (SuperSpeed Gen1 x1), 5 (SuperSpeedPlus Gen2 x1), 6
+    /// (SuperSpeedPlus Gen1 x2), and 7 (SuperSpeedPlus Gen2 x2) for USB3.
+    ///
+    /// This method returns [`None`] if `psiv` is not a default value of the revision.
+    #[must_use]
+    pub fn from_default_id(major_revision: u8, psiv: u8) -> Option<Self> {
+        let (bits_per_second, link_protocol) = match (major_revision, psiv) {
+            (2, 1) => (12_000_000, None),
+            (2, 2) => (1_500_000, None),
+            (2, 3) => (480_000_000, None),
+            (3, 4) => (5_000_000_000, Some(LinkProtocol::SuperSpeed)),
+            (3, 5 | 6) => (10_000_000_000, Some(LinkProtocol::SuperSpeedPlus)),
+            (3, 7) => (20_000_000_000, Some(LinkProtocol::SuperSpeedPlus)),
+            _ => return None,
+        };
+
+        Some(Self {
+            bits_per_second,
+            psi_type: PsiType::Symmetric,
+            full_duplex: major_revision == 3,
+            link_protocol,
+        })
+    }
+
+    /// Decodes `psi` of a protocol whose major revision is `major_revision`.
+    ///
+    /// The Link Protocol field is ignored unless `major_revision == 3`.
+    #[must_use]
+    pub fn from_protocol_speed_id(major_revision: u8, psi: ProtocolSpeedId) -> Self {
+        let unit: u64 = match psi.protocol_speed_id_exponent() {
+            BitRate::Bits => 1,
+            BitRate::Kb => 1_000,
+            BitRate::Mb => 1_000_000,
+            BitRate::Gb => 1_000_000_000,
+        };
+
+        Self {
+            bits_per_second: u64::from(psi.protocol_speed_id_mantissa()) * unit,
+            psi_type: psi.psi_type(),
+            full_duplex: psi.psi_full_duplex(),
+            link_protocol: (major_revision == 3).then(|| psi.link_protocol()),
+        }
+    }
+
+    /// Returns the bit rate in bits per second.
+    #[must_use]
+    pub fn bits_per_second(&self) -> u64 {
+        self.bits_per_second
+    }
+
+    /// Returns whether the bit rate is symmetric, or the direction it applies to.
+    #[must_use]
+    pub fn psi_type(&self) -> PsiType {
+        self.psi_type
+    }
+
+    /// Returns `true` if the link is full-duplex.
+    #[must_use]
+    pub fn full_duplex(&self) -> bool {
+        self.full_duplex
+    }
+
+    /// Returns the link-level protocol.
+    ///
+    /// This method returns [`None`] unless the major revision of the protocol is 3, because the
+    /// Link Protocol field of a Protocol Speed ID is only valid for USB3.
+    #[must_use]
+    pub fn link_protocol(&self) -> Option<LinkProtocol> {
+        self.link_protocol
+    }
+}
+
 /// Bit Rate
 ///
 /// [`ProtocolSpeedId::protocol_speed_id_exponent`] returns a value of this type.
@@ -265,3 +421,82 @@ pub enum LinkProtocol {
     /// Super Speed Plus
     SuperSpeedPlus = 1,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::Identity;
+
+    #[test]
+    fn protocol_speed_id_is_decoded() {
+        // PSIV 6, 10 Gb/s, symmetric, full-duplex, SuperSpeedPlus.
+        let psi = ProtocolSpeedId(10 << 16 | 1 << 14 | 1 << 8 | 3 << 4 | 6);
+        let s = SpeedInfo::from_protocol_speed_id(3, psi);
+
+        assert_eq!(s.bits_per_second(), 10_000_000_000);
+        assert_eq!(s.psi_type(), PsiType::Symmetric);
+        assert!(s.full_duplex());
+        assert_eq!(s.link_protocol(), Some(LinkProtocol::SuperSpeedPlus));
+    }
+
+    #[test]
+    fn usb2_protocol_speed_id_has_no_link_protocol() {
+        // PSIV 3, 480 Mb/s, symmetric, half-duplex, and the Link Protocol field is 0.
+        let psi = ProtocolSpeedId(480 << 16 | 2 << 4 | 3);
+        let s = SpeedInfo::from_protocol_speed_id(2, psi);
+
+        assert_eq!(s.bits_per_second(), 480_000_000);
+        assert!(!s.full_duplex());
+        assert_eq!(s.link_protocol(), None);
+    }
+
+    #[test]
+    fn speed_info_of_usb2_psi_has_no_link_protocol() {
+        // USB 2.0 with a PSI of PSIV 3, 480 Mb/s, whose Link Protocol field is 1.
+        let mut memory = [
+            2 << 24,
+            0x2042_5355,
+            1 << 28,
+            0,
+            480 << 16 | 1 << 14 | 2 << 4 | 3,
+        ];
+        let x = unsafe { XhciSupportedProtocol::new(memory.as_mut_ptr() as usize, Identity) };
+
+        let s = x.speed_info(3).unwrap();
+        assert_eq!(s.bits_per_second(), 480_000_000);
+        assert_eq!(s.link_protocol(), None);
+        assert_eq!(x.speed_info(1), None);
+    }
+
+    #[test]
+    fn name_string_is_decoded() {
+        assert_eq!(Protocol::from(0x2042_5355), Protocol::Usb);
+        assert_eq!(
+            Protocol::from(u32::from_le_bytes(*b"ABCD")),
+            Protocol::Vendor(*b"ABCD")
+        );
+        assert_eq!(u32::from(Protocol::Usb), 0x2042_5355);
+    }
+
+    #[test]
+    fn default_ids_depend_on_revision() {
+        let low = SpeedInfo::from_default_id(2, 2).unwrap();
+        assert_eq!(low.bits_per_second(), 1_500_000);
+        assert!(!low.full_duplex());
+        assert_eq!(low.link_protocol(), None);
+
+        let gen1 = SpeedInfo::from_default_id(3, 4).unwrap();
+        assert_eq!(gen1.bits_per_second(), 5_000_000_000);
+        assert!(gen1.full_duplex());
+        assert_eq!(gen1.link_protocol(), Some(LinkProtocol::SuperSpeed));
+
+        let gen1x2 = SpeedInfo::from_default_id(3, 6).unwrap();
+        assert_eq!(gen1x2.bits_per_second(), 10_000_000_000);
+        assert_eq!(gen1x2.link_protocol(), Some(LinkProtocol::SuperSpeedPlus));
+        let gen2x2 = SpeedInfo::from_default_id(3, 7).unwrap();
+        assert_eq!(gen2x2.bits_per_second(), 20_000_000_000);
+
+        assert_eq!(SpeedInfo::from_default_id(3, 1), None);
+        assert_eq!(SpeedInfo::from_default_id(2, 4), None);
+    }
+}
@@ -0,0 +1,359 @@
+//! Secondary Interrupters.
+//!
+//! The primary interrupter is set up by [`Initializer`](crate::controller::Initializer).
+//! [`InterrupterSet`] allocates the other interrupters, up to the number the xHC supports, each
+//! with its own Event Ring. Events are steered to an interrupter by setting its index to the
+//! Interrupter Target fields of the Slot Context and of Transfer TRBs, which
+//! [`Interrupter::route_slot`] and [`Interrupter::route_trb`] do.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use core::alloc::Layout;
+//! # use xhci::dma::Allocator;
+//! use xhci::context::SlotContextBuilder;
+//! use xhci::interrupter::InterrupterSet;
+//! use xhci::usb::Speed;
+//! #
+//! # #[derive(Clone, Debug)]
+//! # struct DmaAllocator;
+//! # unsafe impl Allocator for DmaAllocator {
+//! #     fn allocate(&mut self, layout: Layout, boundary: usize) -> Option<(usize, u64)> {
+//! #         unimplemented!()
+//! #     }
+//! #
+//! #     unsafe fn deallocate(&mut self, virt: usize, layout: Layout) {
+//! #         unimplemented!()
+//! #     }
+//! # }
+//! #
+//! # #[derive(Clone)]
+//! # struct MemoryMapper;
+//! # impl xhci::accessor::Mapper for MemoryMapper {
+//! #     unsafe fn map(&mut self, phys_base: usize, bytes: usize) -> core::num::NonZeroUsize {
+//! #         unimplemented!()
+//! #     }
+//! #
+//! #     fn unmap(&mut self, virt_base: usize, bytes: usize) {
+//! #         unimplemented!()
+//! #     }
+//! # }
+//! #
+//! # let mut r: xhci::Registers<MemoryMapper> = unimplemented!();
+//!
+//! let mut interrupters =
+//!     InterrupterSet::new(DmaAllocator, r.capability.hcsparams1.read_volatile());
+//! let mut interrupter = interrupters
+//!     .allocate(&mut r)
+//!     .expect("Failed to allocate an interrupter.");
+//!
+//! let mut slot = SlotContextBuilder::new(1, Speed::High);
+//! slot.set_interrupter_target(interrupter.index());
+//!
+//! for event in interrupter.event_ring() {
+//!     // Handle the events of the device.
+//! }
+//! ```
+
+use crate::context::SlotHandler;
+use crate::dma::{Allocator, DmaBox, Requirement};
+use crate::registers::capability::StructuralParameters1;
+use crate::registers::Registers;
+use crate::ring::event::{EventRing, EventRingSegmentTableEntry};
+use crate::ring::trb::transfer;
+use crate::ring::Segment;
+use accessor::array::BoundSetGeneric;
+use accessor::Mapper;
+use core::convert::TryInto;
+use core::mem::ManuallyDrop;
+
+/// The maximum number of interrupters.
+const MAX_INTERRUPTERS: usize = 1024;
+
+/// The allocator of the secondary interrupters.
+///
+/// See the [module-level documentation](self) for the usage.
+#[derive(Debug)]
+pub struct InterrupterSet<A>
+where
+    A: Allocator + Clone,
+{
+    allocator: A,
+    max_interrupters: u16,
+    event_ring_size: u16,
+    allocated: [u64; MAX_INTERRUPTERS / 64],
+}
+impl<A> InterrupterSet<A>
+where
+    A: Allocator + Clone,
+{
+    /// Creates an allocator of the interrupters the xHC supports.
+    ///
+    /// The interrupter 0 is the primary one and is never allocated. Each Event Ring contains 256
+    /// TRBs by default.
+    ///
+    /// The Number of Interrupters field may exceed 1024, the number of the Interrupter Register
+    /// Sets, in which case only the first 1024 interrupters are allocated.
+    pub fn new(allocator: A, hcsparams1: StructuralParameters1) -> Self {
+        let mut allocated = [0; MAX_INTERRUPTERS / 64];
+        allocated[0] = 1;
+
+        Self {
+            allocator,
+            max_interrupters: hcsparams1
+                .number_of_interrupts()
+                .min(MAX_INTERRUPTERS.try_into().unwrap()),
+            event_ring_size: 256,
+            allocated,
+        }
+    }
+
+    /// Sets the number of TRBs of the Event Rings allocated after this call.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `trbs` is not within `16..=4096`.
+    pub fn set_event_ring_size(&mut self, trbs: u16) -> &mut Self {
+        assert!(
+            (EventRingSegmentTableEntry::MIN_RING_SEGMENT_SIZE
+                ..=EventRingSegmentTableEntry::MAX_RING_SEGMENT_SIZE)
+                .contains(&trbs),
+            "The Event Ring must contain 16 to 4096 TRBs."
+        );
+
+        self.event_ring_size = trbs;
+        self
+    }
+
+    /// Returns the number of interrupters this allocator manages, including the primary one.
+    #[must_use]
+    pub fn max_interrupters(&self) -> u16 {
+        self.max_interrupters
+    }
+
+    /// Allocates a free interrupter and sets up its Event Ring.
+    ///
+    /// The Interrupt Enable bit of the interrupter is not set.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`Error::Exhausted`] if all interrupters are allocated, and
+    /// [`Error::AllocationFailed`] if the allocator fails to allocate memory.
+    pub fn allocate<M>(&mut self, r: &mut Registers<M>) -> Result<Interrupter<A>, Error>
+    where
+        M: Mapper + Clone,
+    {
+        let index = (1..self.max_interrupters)
+            .find(|&i| !self.is_allocated(i))
+            .ok_or(Error::Exhausted)?;
+
+        let (event_ring, memory) =
+            new_event_ring(&self.allocator, self.event_ring_size).ok_or(Error::AllocationFailed)?;
+        install(r, index.into(), &event_ring);
+
+        self.set_allocated(index, true);
+
+        Ok(Interrupter {
+            index,
+            event_ring,
+            memory: ManuallyDrop::new(memory),
+        })
+    }
+
+    /// Disables `interrupter`, detaches its Event Ring from the xHC, and frees it.
+    ///
+    /// No Slot Context or TRB may target the interrupter after this call.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `interrupter` is not allocated by this allocator.
+    pub fn free<M>(&mut self, r: &mut Registers<M>, interrupter: Interrupter<A>)
+    where
+        M: Mapper + Clone,
+    {
+        let index = interrupter.index;
+        assert!(
+            self.is_allocated(index),
+            "The interrupter is not allocated by this allocator."
+        );
+
+        let mut registers = r.interrupter_register_set.set_at(index.into());
+        registers.iman.update_volatile(|i| {
+            i.set_0_interrupt_pending().clear_interrupt_enable();
+        });
+        registers.erstsz.update_volatile(|s| {
+            s.set(0);
+        });
+
+        self.set_allocated(index, false);
+
+        let Interrupter { memory, .. } = interrupter;
+        drop(ManuallyDrop::into_inner(memory));
+    }
+
+    fn is_allocated(&self, index: u16) -> bool {
+        let i = usize::from(index);
+        self.allocated[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    fn set_allocated(&mut self, index: u16, allocated: bool) {
+        let i = usize::from(index);
+        if allocated {
+            self.allocated[i / 64] |= 1 << (i % 64);
+        } else {
+            self.allocated[i / 64] &= !(1 << (i % 64));
+        }
+    }
+}
+
+/// A secondary interrupter and its Event Ring.
+///
+/// The xHC may write events to the Event Ring until the interrupter is detached, so dropping this
+/// struct leaks the memory of the Event Ring and the interrupter. Return it with
+/// [`InterrupterSet::free`] to reuse them.
+#[derive(Debug)]
+pub struct Interrupter<A>
+where
+    A: Allocator + Clone,
+{
+    index: u16,
+    event_ring: EventRing<1>,
+    memory: ManuallyDrop<EventRingMemory<A>>,
+}
+impl<A> Interrupter<A>
+where
+    A: Allocator + Clone,
+{
+    /// Returns the index of the interrupter, which is the value of the Interrupter Target fields.
+    #[must_use]
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// Returns the Event Ring of the interrupter.
+    ///
+    /// Write [`EventRing::erdp`] to the Event Ring Dequeue Pointer register of the interrupter
+    /// after handling events.
+    pub fn event_ring(&mut self) -> &mut EventRing<1> {
+        &mut self.event_ring
+    }
+
+    /// Sets the Interrupter Target field of the Slot Context so that the events of the device
+    /// which are not caused by a TRB are sent to this interrupter.
+    pub fn route_slot<S: SlotHandler + ?Sized>(&self, cx: &mut S) {
+        cx.set_interrupter_target(self.index);
+    }
+
+    /// Sets the Interrupter Target field of `trb` so that the events caused by `trb` are sent to
+    /// this interrupter.
+    pub fn route_trb(&self, trb: &mut transfer::Allowed) {
+        trb.set_interrupter_target(self.index);
+    }
+}
+
+/// An error returned by [`InterrupterSet::allocate`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub enum Error {
+    /// All interrupters are allocated.
+    Exhausted,
+    /// The allocator failed to allocate memory.
+    AllocationFailed,
+}
+
+/// The segment and the Event Ring Segment Table.
+pub(crate) type EventRingMemory<A> = (
+    DmaBox<[[u32; 4]], A>,
+    DmaBox<[EventRingSegmentTableEntry; 1], A>,
+);
+
+/// Allocates an Event Ring with a segment of `trbs` TRBs.
+pub(crate) fn new_event_ring<A>(
+    allocator: &A,
+    trbs: u16,
+) -> Option<(EventRing<1>, EventRingMemory<A>)>
+where
+    A: Allocator + Clone,
+{
+    let memory = DmaBox::new_slice(
+        trbs.into(),
+        allocator.clone(),
+        Requirement::EVENT_RING_SEGMENT,
+    )?;
+    // SAFETY: `memory` is returned together with the ring.
+    let segment = unsafe { Segment::new(memory.virt_addr(), memory.phys_addr(), memory.len()) };
+    let erst = DmaBox::new(
+        [EventRingSegmentTableEntry::from_segment(&segment)],
+        allocator.clone(),
+        Requirement::EVENT_RING_SEGMENT_TABLE,
+    )?;
+    // SAFETY: `erst` is returned together with the ring.
+    let event_ring = unsafe { EventRing::new(erst.virt_addr(), erst.phys_addr(), [segment]) };
+
+    Some((event_ring, (memory, erst)))
+}
+
+/// Writes the registers of the Event Ring of the interrupter `index`.
+///
+/// Writing ERSTBA enables the Event Ring, so ERSTSZ and ERDP are written before it.
+pub(crate) fn install<M>(r: &mut Registers<M>, index: usize, event_ring: &EventRing<1>)
+where
+    M: Mapper + Clone,
+{
+    let mut registers = r.interrupter_register_set.set_at(index);
+    registers.erstsz.update_volatile(|s| {
+        s.set(event_ring.erst_size());
+    });
+    registers.erdp.write_volatile(event_ring.erdp());
+    registers.erstba.update_volatile(|b| {
+        b.set(event_ring.erst_base_address());
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::dma::Heap;
+    use crate::test_util::registers;
+    use alloc::vec;
+
+    fn hcsparams1(interrupters: u32) -> StructuralParameters1 {
+        StructuralParameters1::from_raw(interrupters << 8)
+    }
+
+    #[test]
+    fn freed_interrupter_is_reused() {
+        let mut mmio = vec![0; 0x2000];
+        let mut r = registers(&mut mmio);
+        let mut set = InterrupterSet::new(Heap, hcsparams1(3));
+        set.set_event_ring_size(16);
+
+        let a = set.allocate(&mut r).unwrap();
+        let b = set.allocate(&mut r).unwrap();
+        assert_eq!((a.index(), b.index()), (1, 2));
+        assert_eq!(set.allocate(&mut r).unwrap_err(), Error::Exhausted);
+        let registers = r.interrupter_register_set.read_volatile_at(1);
+        assert_eq!(registers.erstsz.get(), 1);
+        let erstba = registers.erstba.get();
+        assert_ne!(erstba, 0);
+
+        set.free(&mut r, a);
+        // Only IMAN and ERSTSZ are written.
+        let registers = r.interrupter_register_set.read_volatile_at(1);
+        assert_eq!(registers.erstsz.get(), 0);
+        assert_eq!(registers.erstba.get(), erstba);
+        assert_eq!(set.allocate(&mut r).unwrap().index(), 1);
+    }
+
+    #[test]
+    fn interrupters_beyond_register_sets_are_not_allocated() {
+        let mut mmio = vec![0; 0x2000];
+        let mut r = registers(&mut mmio);
+        let mut set = InterrupterSet::new(Heap, hcsparams1(2047));
+        assert_eq!(set.max_interrupters(), 1024);
+
+        set.allocated = [u64::MAX; MAX_INTERRUPTERS / 64];
+        set.set_allocated(1000, false);
+        assert_eq!(set.allocate(&mut r).unwrap().index(), 1000);
+        assert_eq!(set.allocate(&mut r).unwrap_err(), Error::Exhausted);
+    }
+}
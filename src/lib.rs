@@ -3,7 +3,8 @@
 //! This crate provides types of the xHCI structures, such as the Registers and Contexts.
 //! Users can use this library to implement a USB device deriver on your own OS.
 //!
-//! This crate is `#![no_std]` compatible.
+//! This crate is `#![no_std]` compatible. Enabling the `alloc` feature adds `dma::Heap`, an
+//! allocator backed by the global heap.
 //!
 //! # Examples
 //!
@@ -35,6 +36,9 @@
 //! });
 //! while o.usbsts.read().hc_halted() {}
 //! ```
+//!
+//! The [`controller`] module performs the whole initialization sequence, including the setup of
+//! the Command Ring and the Event Ring.
 
 #![no_std]
 #![deny(
@@ -60,6 +64,9 @@
 )]
 #![allow(clippy::missing_panics_doc)]
 
+#[cfg(any(test, feature = "alloc"))]
+extern crate alloc;
+
 pub use accessor;
 pub use extended_capabilities::ExtendedCapability;
 pub use registers::Registers;
@@ -68,6 +75,15 @@ pub use registers::Registers;
 mod macros;
 
 pub mod context;
+pub mod controller;
+pub mod dma;
+pub mod enumerator;
 pub mod extended_capabilities;
+pub mod interrupter;
 pub mod registers;
 pub mod ring;
+pub mod root_hub;
+pub mod usb;
+
+#[cfg(test)]
+mod test_util;
@@ -109,6 +109,14 @@ impl StructuralParameters1 {
     ro_field!(8..=18, number_of_interrupts, "Number of Interrupts", u16);
     ro_field!(24..=31, number_of_ports, "Number of Ports", u8);
 }
+
+#[cfg(test)]
+impl StructuralParameters1 {
+    pub(crate) fn from_raw(raw: u32) -> Self {
+        Self(raw)
+    }
+}
+
 impl_debug_from_methods! {
     StructuralParameters1{
         number_of_device_slots,
@@ -161,6 +169,14 @@ impl StructuralParameters2 {
         self.0.get_bits(27..=31)
     }
 }
+
+#[cfg(test)]
+impl StructuralParameters2 {
+    pub(crate) fn from_raw(raw: u32) -> Self {
+        Self(raw)
+    }
+}
+
 impl_debug_from_methods! {
     StructuralParameters2{
         isochronous_scheduling_threshold,
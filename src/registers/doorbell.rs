@@ -12,6 +12,9 @@ pub struct Doorbell(u32);
 impl Doorbell {
     /// Creates a new accessor to the Doorbell Array.
     ///
+    /// The array has `MaxSlots` + 1 elements. The 0th element is the Host Controller doorbell, and
+    /// the `n`th element is the doorbell of the Device Slot `n`.
+    ///
     /// # Safety
     ///
     /// Caller must ensure that the only one accessor is created, otherwise it may cause undefined
@@ -30,15 +33,28 @@ impl Doorbell {
         M2: Mapper + Clone,
     {
         let base = mmio_base + usize::try_from(capability.dboff.read_volatile().get()).unwrap();
-        array::ReadWrite::new(
-            base,
-            capability
-                .hcsparams1
-                .read_volatile()
-                .number_of_device_slots()
-                .into(),
-            mapper,
-        )
+        let max_slots = capability
+            .hcsparams1
+            .read_volatile()
+            .number_of_device_slots();
+        array::ReadWrite::new(base, usize::from(max_slots) + 1, mapper)
+    }
+
+    /// Returns the value to write to the Host Controller doorbell to notify the xHC that Command
+    /// TRBs are enqueued on the Command Ring.
+    #[must_use]
+    pub fn command_ring() -> Self {
+        Self::default()
+    }
+
+    /// Returns the value to write to the doorbell of a Device Slot to notify the xHC that TRBs are
+    /// enqueued on the Transfer Ring specified by `target`.
+    #[must_use]
+    pub fn endpoint(target: EndpointTarget) -> Self {
+        let mut d = Self::default();
+        d.set_doorbell_target(target.dci);
+        d.set_doorbell_stream_id(target.stream_id);
+        d
     }
 
     rw_field!(0..=7, doorbell_target, "Doorbell Target", u8);
@@ -52,3 +68,62 @@ impl fmt::Debug for Doorbell {
             .finish()
     }
 }
+
+/// The Transfer Ring whose doorbell is rung.
+///
+/// This is the typed form of the Doorbell Target and the Doorbell Stream ID fields for the doorbell
+/// of a Device Slot.
+#[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct EndpointTarget {
+    dci: u8,
+    stream_id: u16,
+}
+impl EndpointTarget {
+    /// Creates a new target which specifies the Transfer Ring of the Endpoint Context `dci` and the
+    /// stream `stream_id`.
+    ///
+    /// Pass 0 as `stream_id` if the endpoint does not use streams.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `dci` is not within `1..=31`.
+    #[must_use]
+    pub fn new(dci: u8, stream_id: u16) -> Self {
+        assert!(
+            (1..=31).contains(&dci),
+            "The Device Context Index must be within 1..=31."
+        );
+
+        Self { dci, stream_id }
+    }
+
+    /// Returns the Device Context Index of the endpoint.
+    #[must_use]
+    pub fn dci(self) -> u8 {
+        self.dci
+    }
+
+    /// Returns the Stream ID.
+    #[must_use]
+    pub fn stream_id(self) -> u16 {
+        self.stream_id
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn endpoint_doorbell_is_encoded() {
+        let d = Doorbell::endpoint(EndpointTarget::new(3, 0x1234));
+        assert_eq!(d.0, 0x1234_0003);
+        assert_eq!(Doorbell::command_ring().0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "The Device Context Index must be within 1..=31.")]
+    fn dci_0_is_rejected() {
+        let _ = EndpointTarget::new(0, 0);
+    }
+}
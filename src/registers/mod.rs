@@ -1,10 +1,12 @@
 //! xHCI registers
 
-use accessor::array;
+use accessor::array::{self, BoundSetGeneric};
 use accessor::Mapper;
+use operational::{PortChanges, PortStatusAndControlRegister};
+use runtime::{InterrupterModerationRegister, Moderation};
 
 pub use capability::Capability;
-pub use doorbell::Doorbell;
+pub use doorbell::{Doorbell, EndpointTarget};
 pub use operational::{Operational, PortRegisterSet};
 pub use runtime::InterrupterRegisterSet;
 pub use runtime::Runtime;
@@ -95,4 +97,183 @@ where
             interrupter_register_set,
         }
     }
+
+    /// Modifies the Port Status and Control Register of the Root Hub Port `port_id` without
+    /// clearing the pending change bits.
+    ///
+    /// `f` receives the value returned by
+    /// [`PortStatusAndControlRegister::write_preserving`], so the RW1C and RW1S bits are written as 1
+    /// only if `f` sets them explicitly.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `port_id` is 0 or greater than the number of ports.
+    pub fn portsc_modify<F>(&mut self, port_id: u8, f: F)
+    where
+        F: FnOnce(&mut PortStatusAndControlRegister),
+    {
+        assert_ne!(port_id, 0, "The Port ID must not be 0.");
+
+        self.port_register_set
+            .set_at((port_id - 1).into())
+            .portsc
+            .update_volatile(|p| {
+                let mut portsc = p.write_preserving();
+                f(&mut portsc);
+                *p = portsc;
+            });
+    }
+
+    /// Clears the change bits in `mask` which are set in the Port Status and Control Register of
+    /// the Root Hub Port `port_id`, and returns the cleared ones.
+    ///
+    /// The other change bits are preserved.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `port_id` is 0 or greater than the number of ports.
+    pub fn acknowledge_changes(&mut self, port_id: u8, mask: PortChanges) -> PortChanges {
+        assert_ne!(port_id, 0, "The Port ID must not be 0.");
+
+        let changes = self
+            .port_register_set
+            .set_at((port_id - 1).into())
+            .portsc
+            .read_volatile()
+            .changes()
+            & mask;
+        if !changes.is_empty() {
+            self.portsc_modify(port_id, |p| {
+                p.acknowledge(changes);
+            });
+        }
+
+        changes
+    }
+
+    /// Enables the interrupts of the interrupter `index` with the moderation interval
+    /// `moderation`.
+    ///
+    /// Only the Interrupter Management and the Interrupter Moderation Registers are written. The
+    /// Interrupt Moderation Counter is reset so that the next interrupt is not delayed, and the
+    /// pending interrupt is not cleared.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `index` is not less than the number of interrupters.
+    pub fn enable_interrupts(&mut self, index: u16, moderation: Moderation) {
+        let mut registers = self.interrupter_register_set.set_at(index.into());
+        let mut imod = InterrupterModerationRegister::default();
+        imod.set_moderation(moderation);
+        registers.imod.write_volatile(imod);
+        registers.iman.update_volatile(|i| {
+            i.set_0_interrupt_pending().set_interrupt_enable();
+        });
+    }
+
+    /// Disables the interrupts of the interrupter `index`.
+    ///
+    /// Only the Interrupter Management Register is written, and the pending interrupt is not
+    /// cleared.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `index` is not less than the number of interrupters.
+    pub fn disable_interrupts(&mut self, index: u16) {
+        self.interrupter_register_set
+            .set_at(index.into())
+            .iman
+            .update_volatile(|i| {
+                i.set_0_interrupt_pending().clear_interrupt_enable();
+            });
+    }
+
+    /// Acknowledges the interrupt of the interrupter `index`, and returns `true` if the interrupt
+    /// was pending.
+    ///
+    /// Only the Interrupter Management Register is written; see
+    /// [`InterrupterManagementRegister::acknowledge`](runtime::InterrupterManagementRegister::acknowledge).
+    /// The Event Handler Busy bit is cleared by writing
+    /// [`EventRing::erdp`](crate::ring::event::EventRing::erdp) after handling the events.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `index` is not less than the number of interrupters.
+    pub fn acknowledge_interrupt(&mut self, index: u16) -> bool {
+        let mut pending = false;
+        self.interrupter_register_set
+            .set_at(index.into())
+            .iman
+            .update_volatile(|i| {
+                pending = i.acknowledge();
+            });
+
+        pending
+    }
+
+    /// Rings the Host Controller doorbell to notify the xHC that Command TRBs are enqueued.
+    pub fn ring_command(&mut self) {
+        self.doorbell.write_volatile_at(0, Doorbell::command_ring());
+    }
+
+    /// Rings the doorbell of the Device Slot `slot_id` to notify the xHC that TRBs are enqueued on
+    /// the Transfer Ring specified by `target`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `slot_id` is 0 or greater than the number of Device Slots.
+    pub fn ring_endpoint(&mut self, slot_id: u8, target: EndpointTarget) {
+        assert_ne!(slot_id, 0, "The doorbell 0 is for the Host Controller.");
+
+        self.doorbell
+            .write_volatile_at(slot_id.into(), Doorbell::endpoint(target));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::registers;
+    use alloc::vec;
+
+    #[test]
+    fn acknowledge_changes_writes_only_portsc() {
+        let mut mmio = vec![0; 0x2000];
+        // The PORTSC of the port 2 has the Connect Status and Port Reset Changes pending, and its
+        // PORTPMSC is 0xdead.
+        mmio[0x86] = (1 | 1 << 9 | 1 << 17 | 1 << 21) | 0xdead << 32;
+        let mut r = registers(&mut mmio);
+
+        let acked = r.acknowledge_changes(2, PortChanges::CONNECT_STATUS);
+        drop(r);
+
+        assert_eq!(acked, PortChanges::CONNECT_STATUS);
+        // Only the acknowledged change bit is written as 1.
+        assert_eq!(mmio[0x86], (1 | 1 << 9 | 1 << 17) | 0xdead << 32);
+    }
+
+    #[test]
+    fn interrupter_methods_write_only_iman_and_imod() {
+        let mut mmio = vec![0; 0x2000];
+        // The Interrupter Register Set 1 is at 0x4040. Its interrupt is pending, the Interrupt
+        // Moderation Counter is 0x1234, and the Event Handler Busy bit is set.
+        let set = 0x808;
+        mmio[set] = 0x1234 << 48 | 1;
+        mmio[set + 1] = 1;
+        mmio[set + 2] = 0xdead_0000;
+        mmio[set + 3] = 0xbeef_0008;
+        let mut r = registers(&mut mmio);
+
+        assert!(r.acknowledge_interrupt(1));
+        r.enable_interrupts(1, Moderation::from_ticks(4000));
+        assert!(!r.acknowledge_interrupt(1));
+        drop(r);
+        assert_eq!(mmio[set], 4000 << 32 | 0b10);
+
+        let mut r = registers(&mut mmio);
+        r.disable_interrupts(1);
+        drop(r);
+        assert_eq!(mmio[set], 4000 << 32);
+        assert_eq!(mmio[set + 1..set + 4], [1, 0xdead_0000, 0xbeef_0008]);
+    }
 }
@@ -1,12 +1,16 @@
 //! Host Controller Operational Registers
 
 use super::capability::{Capability, CapabilityRegistersLength};
+use crate::extended_capabilities::xhci_supported_protocol::{
+    ProtocolSpeedId, SpeedInfo, XhciSupportedProtocol,
+};
 use accessor::array::{self, BoundSetGenericOf};
 use accessor::single;
 use accessor::Mapper;
 use bit_field::BitField;
 use core::convert::TryFrom;
 use core::convert::TryInto;
+use core::ops::{BitAnd, BitOr, BitOrAssign};
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 
@@ -156,6 +160,22 @@ impl PageSizeRegister {
     pub fn get(self) -> u16 {
         self.0.try_into().unwrap()
     }
+
+    /// Returns the page size supported by xHC in bytes.
+    ///
+    /// The Page Size field stores the page size as a bit, where the `n`th bit means `2^(n+12)`
+    /// bytes. This method returns the calculated value.
+    #[must_use]
+    pub fn bytes(self) -> usize {
+        1 << (self.get().trailing_zeros() + 12)
+    }
+}
+
+#[cfg(test)]
+impl PageSizeRegister {
+    pub(crate) fn from_raw(raw: u32) -> Self {
+        Self(raw)
+    }
 }
 
 /// Device Notification Control
@@ -344,9 +364,32 @@ impl PortStatusAndControlRegister {
     rw1c_bit!(1, port_enabled_disabled, "Port Enabled/Disabled");
     ro_bit!(3, over_current_active, "Over-current Active");
     rw1s_bit!(4, port_reset, "Port Reset");
-    rw_field!(5..=8, port_link_state, "Port Link State", u8);
+
+    /// Returns the value of the Port Link State field.
+    ///
+    /// This method returns [`None`] if the value means `Reserved`.
+    #[must_use]
+    pub fn port_link_state(self) -> Option<PortLinkState> {
+        FromPrimitive::from_u32(self.0.get_bits(5..=8))
+    }
+
+    /// Sets the value of the Port Link State field.
+    ///
+    /// This method also sets the Port Link State Write Strobe bit so that the xHC accepts the
+    /// written value.
+    pub fn set_port_link_state(&mut self, state: PortLinkState) -> &mut Self {
+        self.0.set_bits(5..=8, state.into());
+        self.set_port_link_state_write_strobe()
+    }
+
     rw_bit!(9, port_power, "Port Power");
-    ro_field!(10..=13, port_speed, "Port Speed", u8);
+
+    /// Returns the value of the Port Speed field.
+    #[must_use]
+    pub fn port_speed(self) -> PortSpeed {
+        PortSpeed(self.0.get_bits(10..=13).try_into().unwrap())
+    }
+
     rw_field!(
         14..=15,
         port_indicator_control,
@@ -379,7 +422,44 @@ impl PortStatusAndControlRegister {
     );
     ro_bit!(30, device_removable, "Device Removable");
     rw1s_bit!(31, warm_port_reset, "Warm Port Reset");
+
+    /// Returns the change bits which are set.
+    #[must_use]
+    pub fn changes(self) -> PortChanges {
+        PortChanges(self.0 & PortChanges::ALL.0)
+    }
+
+    /// Returns a copy of the register which can be written back without side effects.
+    ///
+    /// Writing back the value read from the register clears all pending change bits and disables
+    /// the port because the Port Enabled/Disabled bit and the change bits are RW1C. The returned
+    /// value has those bits, the RW1S bits (Port Reset and Warm Port Reset), and the Port Link State
+    /// Write Strobe bit cleared so that writing it changes only the RW fields.
+    #[must_use]
+    pub fn write_preserving(self) -> Self {
+        let mut r = self;
+        r.0 &= !(Self::RW1C_BITS | Self::RW1S_BITS);
+        r.clear_port_link_state_write_strobe();
+        r
+    }
+
+    /// Sets the change bits specified by `changes` to 1 so that writing the register clears them.
+    pub fn acknowledge(&mut self, changes: PortChanges) -> &mut Self {
+        self.0 |= changes.0;
+        self
+    }
+
+    const RW1C_BITS: u32 = PortChanges::ALL.0 | 1 << 1;
+    const RW1S_BITS: u32 = 1 << 4 | 1 << 31;
+}
+
+#[cfg(test)]
+impl PortStatusAndControlRegister {
+    pub(crate) fn from_raw(raw: u32) -> Self {
+        Self(raw)
+    }
 }
+
 impl_debug_from_methods! {
     PortStatusAndControlRegister{
         current_connect_status,
@@ -539,6 +619,163 @@ impl From<PortIndicator> for u32 {
     }
 }
 
+/// Port Link State.
+///
+/// [`PortStatusAndControlRegister::port_link_state`] returns a value of this type.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, FromPrimitive)]
+pub enum PortLinkState {
+    /// U0 State.
+    U0 = 0,
+    /// U1 State.
+    U1 = 1,
+    /// U2 State.
+    U2 = 2,
+    /// U3 State (Device Suspended).
+    U3 = 3,
+    /// Disabled State.
+    Disabled = 4,
+    /// `RxDetect` State.
+    RxDetect = 5,
+    /// Inactive State.
+    Inactive = 6,
+    /// Polling State.
+    Polling = 7,
+    /// Recovery State.
+    Recovery = 8,
+    /// Hot Reset State.
+    HotReset = 9,
+    /// Compliance Mode State.
+    ComplianceMode = 10,
+    /// Test Mode State.
+    TestMode = 11,
+    /// Resume State.
+    Resume = 15,
+}
+impl TryFrom<u32> for PortLinkState {
+    type Error = u32;
+    fn try_from(x: u32) -> Result<Self, Self::Error> {
+        FromPrimitive::from_u32(x).ok_or(x)
+    }
+}
+impl From<PortLinkState> for u32 {
+    fn from(s: PortLinkState) -> Self {
+        s as _
+    }
+}
+
+/// The speed of a port.
+///
+/// This type holds the Protocol Speed ID Value (PSIV) which the xHC reports. The meaning of the
+/// value is defined by the Protocol Speed ID table of the xHCI Supported Protocol Capability which
+/// covers the port.
+///
+/// [`PortStatusAndControlRegister::port_speed`] returns a value of this type.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct PortSpeed(pub(crate) u8);
+impl PortSpeed {
+    /// Returns the Protocol Speed ID Value.
+    ///
+    /// The value 0 means that no device is connected or the speed is undefined.
+    #[must_use]
+    pub fn protocol_speed_id_value(self) -> u8 {
+        self.0
+    }
+
+    /// Looks up the Protocol Speed ID which has the same Protocol Speed ID Value as this speed.
+    ///
+    /// `protocol` must be the xHCI Supported Protocol Capability whose compatible ports include
+    /// the port. See [`XhciSupportedProtocol::protocol_speed_id`] for the details.
+    #[must_use]
+    pub fn protocol_speed_id<M>(
+        self,
+        protocol: &XhciSupportedProtocol<M>,
+    ) -> Option<ProtocolSpeedId>
+    where
+        M: Mapper + Clone,
+    {
+        protocol.protocol_speed_id(self.0)
+    }
+
+    /// Returns the bit rate and the other properties of this speed.
+    ///
+    /// `protocol` must be the xHCI Supported Protocol Capability whose compatible ports include
+    /// the port. Unlike [`PortSpeed::protocol_speed_id`], this method also resolves the default
+    /// Protocol Speed ID Values if `protocol` does not define any Protocol Speed IDs.
+    #[must_use]
+    pub fn speed_info<M>(self, protocol: &XhciSupportedProtocol<M>) -> Option<SpeedInfo>
+    where
+        M: Mapper + Clone,
+    {
+        protocol.speed_info(self.0)
+    }
+}
+impl From<PortSpeed> for u8 {
+    fn from(s: PortSpeed) -> Self {
+        s.0
+    }
+}
+
+/// A set of the change bits of [`PortStatusAndControlRegister`].
+///
+/// [`PortStatusAndControlRegister::changes`] returns a value of this type.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct PortChanges(u32);
+impl PortChanges {
+    /// Connect Status Change.
+    pub const CONNECT_STATUS: Self = Self(1 << 17);
+    /// Port Enabled/Disabled Change.
+    pub const PORT_ENABLED_DISABLED: Self = Self(1 << 18);
+    /// Warm Port Reset Change.
+    pub const WARM_PORT_RESET: Self = Self(1 << 19);
+    /// Over-current Change.
+    pub const OVER_CURRENT: Self = Self(1 << 20);
+    /// Port Reset Change.
+    pub const PORT_RESET: Self = Self(1 << 21);
+    /// Port Link State Change.
+    pub const PORT_LINK_STATE: Self = Self(1 << 22);
+    /// Port Config Error Change.
+    pub const PORT_CONFIG_ERROR: Self = Self(1 << 23);
+    /// All change bits.
+    pub const ALL: Self = Self(0x00fe_0000);
+
+    /// Returns an empty set.
+    #[must_use]
+    pub fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Returns `true` if no change bits are in the set.
+    #[must_use]
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns `true` if all change bits in `other` are in the set.
+    #[must_use]
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+impl BitOr for PortChanges {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+impl BitOrAssign for PortChanges {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+impl BitAnd for PortChanges {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self {
+        Self(self.0 & rhs.0)
+    }
+}
+
 /// L1 Status.
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, FromPrimitive)]
 pub enum L1Status {
@@ -572,3 +809,26 @@ pub enum TestMode {
     /// Port Test Control Error.
     PortTestControlError = 15,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn write_preserving_value_clears_no_change_bits() {
+        // Connected, enabled, powered, and the Connect Status and Port Reset Changes are pending.
+        let r = PortStatusAndControlRegister(1 | 1 << 1 | 1 << 9 | 1 << 17 | 1 << 21);
+        assert_eq!(
+            r.changes(),
+            PortChanges::CONNECT_STATUS | PortChanges::PORT_RESET
+        );
+
+        let mut w = r.write_preserving();
+        assert!(!w.port_enabled_disabled());
+        assert!(w.changes().is_empty());
+        assert!(w.port_power());
+
+        w.acknowledge(PortChanges::PORT_RESET);
+        assert_eq!(w.changes(), PortChanges::PORT_RESET);
+    }
+}
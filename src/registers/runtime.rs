@@ -8,6 +8,7 @@ use accessor::array::{self, BoundSetGenericOf};
 use accessor::Mapper;
 use core::convert::TryFrom;
 use core::convert::TryInto;
+use core::time::Duration;
 
 /// Runtime Registers
 ///
@@ -98,6 +99,7 @@ impl InterrupterRegisterSet {
             mapper,
         )
     }
+
 }
 
 /// Interrupter Management Register.
@@ -108,6 +110,21 @@ impl InterrupterManagementRegister {
     rw1c_bit!(0, interrupt_pending, "Interrupt Pending");
     rw_bit!(1, interrupt_enable, "Interrupt Enable");
 }
+impl InterrupterManagementRegister {
+    /// Prepares the value to acknowledge an interrupt, and returns `true` if the interrupt was
+    /// pending.
+    ///
+    /// The Interrupt Pending bit is RW1C. This method leaves the bit 1 only if it was set, so that
+    /// writing the value back clears exactly the interrupt which was read.
+    pub fn acknowledge(&mut self) -> bool {
+        let pending = self.interrupt_pending();
+        if !pending {
+            self.set_0_interrupt_pending();
+        }
+
+        pending
+    }
+}
 impl_debug_from_methods! {
     InterrupterManagementRegister {
         interrupt_pending,
@@ -133,6 +150,18 @@ impl InterrupterModerationRegister {
         u16
     );
 }
+impl InterrupterModerationRegister {
+    /// Returns the Interrupt Moderation Interval.
+    #[must_use]
+    pub fn moderation(self) -> Moderation {
+        Moderation(self.interrupt_moderation_interval())
+    }
+
+    /// Sets the Interrupt Moderation Interval.
+    pub fn set_moderation(&mut self, moderation: Moderation) -> &mut Self {
+        self.set_interrupt_moderation_interval(moderation.0)
+    }
+}
 impl_debug_from_methods! {
     InterrupterModerationRegister{
         interrupt_moderation_interval,
@@ -140,6 +169,55 @@ impl_debug_from_methods! {
     }
 }
 
+/// The minimum interval between interrupts of an interrupter.
+///
+/// The xHC counts the interval in 250 ns units, so the interval must not exceed
+/// [`Moderation::MAX`], 16.38375 ms. Use [`TryFrom<Duration>`] to create a value from a
+/// [`Duration`]; it returns the given duration as the error if it is too long.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug, Default)]
+pub struct Moderation(u16);
+impl Moderation {
+    /// Interrupts are not moderated.
+    pub const DISABLED: Self = Self(0);
+    /// The longest interval, 16.38375 ms.
+    pub const MAX: Self = Self(u16::MAX);
+
+    /// The unit of the interval.
+    const TICK: Duration = Duration::from_nanos(250);
+
+    /// Creates a value from the interval in 250 ns units.
+    #[must_use]
+    pub fn from_ticks(ticks: u16) -> Self {
+        Self(ticks)
+    }
+
+    /// Returns the interval in 250 ns units.
+    #[must_use]
+    pub fn ticks(self) -> u16 {
+        self.0
+    }
+
+    /// Returns the interval.
+    #[must_use]
+    pub fn interval(self) -> Duration {
+        Self::TICK * u32::from(self.0)
+    }
+}
+impl TryFrom<Duration> for Moderation {
+    type Error = Duration;
+
+    /// Converts `interval` into the moderation interval, rounding it down to a multiple of 250 ns.
+    fn try_from(interval: Duration) -> Result<Self, Self::Error> {
+        let ticks = interval.as_nanos() / Self::TICK.as_nanos();
+        u16::try_from(ticks).map(Self).map_err(|_| interval)
+    }
+}
+impl From<Moderation> for Duration {
+    fn from(m: Moderation) -> Self {
+        m.interval()
+    }
+}
+
 /// Event Ring Segment Table Size Register.
 #[repr(transparent)]
 #[derive(Copy, Clone, Debug)]
@@ -158,6 +236,9 @@ impl EventRingSegmentTableSizeRegister {
 }
 
 /// Event Ring Segment Table Base Address Register.
+///
+/// The table this register points to is an array of
+/// [`EventRingSegmentTableEntry`](crate::ring::event::EventRingSegmentTableEntry).
 #[repr(transparent)]
 #[derive(Copy, Clone, Debug)]
 pub struct EventRingSegmentTableBaseAddressRegister(u64);
@@ -221,3 +302,35 @@ impl_debug_from_methods! {
         event_ring_dequeue_pointer
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn moderation_is_converted_from_duration() {
+        let m = Moderation::try_from(Duration::from_micros(1)).unwrap();
+        assert_eq!(m.ticks(), 4);
+        assert_eq!(m.interval(), Duration::from_micros(1));
+
+        assert_eq!(
+            Moderation::try_from(Duration::from_nanos(16_383_750)),
+            Ok(Moderation::MAX)
+        );
+        assert_eq!(
+            Moderation::try_from(Duration::from_micros(16_384)),
+            Err(Duration::from_micros(16_384))
+        );
+    }
+
+    #[test]
+    fn acknowledge_clears_only_pending_interrupt() {
+        let mut pending = InterrupterManagementRegister(0b11);
+        assert!(pending.acknowledge());
+        assert_eq!(pending.0, 0b11);
+
+        let mut idle = InterrupterManagementRegister(0b10);
+        assert!(!idle.acknowledge());
+        assert_eq!(idle.0, 0b10);
+    }
+}
@@ -0,0 +1,440 @@
+//! The consumer side of Event Rings.
+//!
+//! The xHC is the producer of an Event Ring, and software is the consumer. [`EventRing`] owns the
+//! Event Ring Segment Table, tracks the Dequeue Pointer and the Consumer Cycle State, and yields
+//! the Event TRBs the xHC has written.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use xhci::ring::{event::EventRing, Segment};
+//!
+//! # let (erst_virt, erst_phys) = (0x1000, 0x1000);
+//! # let (virt, phys) = (0x2000, 0x2000);
+//! let segment = unsafe { Segment::new(virt, phys, 256) };
+//! let mut ring = unsafe { EventRing::new(erst_virt, erst_phys, [segment]) };
+//!
+//! // Write `ring.erst_size()` to ERSTSZ, `ring.erdp()` to ERDP, and `ring.erst_base_address()` to
+//! // ERSTBA of an Interrupter Register Set.
+//!
+//! for event in &mut ring {
+//!     match event {
+//!         Ok(event) => { /* Handle the event. */ }
+//!         Err(raw) => { /* This crate does not know the TRB. */ }
+//!     }
+//! }
+//!
+//! // Write `ring.erdp()` to ERDP to notify the xHC of the processed events.
+//! ```
+
+use super::trb::event::Allowed;
+use super::Segment;
+use crate::registers::runtime::EventRingDequeuePointerRegister;
+use bit_field::BitField;
+use core::convert::{TryFrom, TryInto};
+use core::ptr;
+
+/// An Event Ring of an Interrupter.
+///
+/// `N` is the number of segments, which must not exceed the value of
+/// [`StructuralParameters2::event_ring_segment_table_max`](crate::registers::capability::StructuralParameters2::event_ring_segment_table_max).
+#[derive(Debug)]
+pub struct EventRing<const N: usize> {
+    erst_phys: u64,
+    segments: [Segment; N],
+    dequeue: Position,
+    cycle_state: bool,
+}
+impl<const N: usize> EventRing<N> {
+    /// Creates a new Event Ring over `segments`, and writes its Event Ring Segment Table to the
+    /// memory starting at `erst_virt`.
+    ///
+    /// This method fills all segments with 0. The Consumer Cycle State is initialized to 1.
+    ///
+    /// # Safety
+    ///
+    /// `erst_virt` must be the virtual address of a memory block that can hold `N` Event Ring
+    /// Segment Table Entries, and `erst_phys` must be the physical address of the same block. The
+    /// block must stay accessible while the returned ring exists, and it must be accessed only
+    /// through the ring.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `N == 0`, if `erst_virt` or `erst_phys` is not 64-byte aligned, or
+    /// if a segment does not meet the conditions described in
+    /// [`EventRingSegmentTableEntry::from_segment`].
+    #[must_use]
+    pub unsafe fn new(erst_virt: usize, erst_phys: u64, segments: [Segment; N]) -> Self {
+        assert_ne!(N, 0, "An Event Ring must have at least one segment.");
+        assert_eq!(
+            erst_virt % 64,
+            0,
+            "The Event Ring Segment Table must be 64-byte aligned."
+        );
+        assert_eq!(
+            erst_phys % 64,
+            0,
+            "The Event Ring Segment Table must be 64-byte aligned."
+        );
+
+        let erst = erst_virt as *mut EventRingSegmentTableEntry;
+        for (i, s) in segments.iter().enumerate() {
+            s.clear();
+            ptr::write_volatile(erst.add(i), EventRingSegmentTableEntry::from_segment(s));
+        }
+
+        Self {
+            erst_phys,
+            segments,
+            dequeue: Position::default(),
+            cycle_state: true,
+        }
+    }
+
+    /// Returns the physical address of the Event Ring Segment Table.
+    ///
+    /// Write this value to the ERSTBA register.
+    #[must_use]
+    pub fn erst_base_address(&self) -> u64 {
+        self.erst_phys
+    }
+
+    /// Returns the number of the Event Ring Segment Table entries.
+    ///
+    /// Write this value to the ERSTSZ register.
+    #[must_use]
+    pub fn erst_size(&self) -> u16 {
+        N.try_into()
+            .expect("The number of segments must fit in u16.")
+    }
+
+    /// Returns the physical address of the next Event TRB the ring will yield.
+    #[must_use]
+    pub fn dequeue_pointer(&self) -> u64 {
+        self.segments[self.dequeue.segment].trb_address(self.dequeue.index)
+    }
+
+    /// Returns the index of the segment the Dequeue Pointer points to.
+    #[must_use]
+    pub fn dequeue_segment_index(&self) -> usize {
+        self.dequeue.segment
+    }
+
+    /// Returns the Consumer Cycle State.
+    #[must_use]
+    pub fn cycle_state(&self) -> bool {
+        self.cycle_state
+    }
+
+    /// Returns the value to write to the ERDP register.
+    ///
+    /// The value contains the current Dequeue Pointer and the Dequeue ERST Segment Index, and has
+    /// the Event Handler Busy bit set so that writing it clears the bit. Write this value after
+    /// processing a batch of events.
+    #[must_use]
+    pub fn erdp(&self) -> EventRingDequeuePointerRegister {
+        let mut r = EventRingDequeuePointerRegister::default();
+        r.set_event_ring_dequeue_pointer(self.dequeue_pointer());
+        r.set_dequeue_erst_segment_index(self.dequeue.segment.get_bits(0..3).try_into().unwrap());
+        r.clear_event_handler_busy();
+        r
+    }
+
+    fn advance(&mut self) {
+        if self.dequeue.index + 1 < self.segments[self.dequeue.segment].number_of_trbs() {
+            self.dequeue.index += 1;
+        } else {
+            self.dequeue.index = 0;
+            self.dequeue.segment = (self.dequeue.segment + 1) % N;
+
+            if self.dequeue.segment == 0 {
+                self.cycle_state = !self.cycle_state;
+            }
+        }
+    }
+}
+impl<const N: usize> Iterator for EventRing<N> {
+    type Item = Result<Allowed, [u32; 4]>;
+
+    /// Dequeues the next Event TRB.
+    ///
+    /// This method returns [`None`] if the xHC has not written a new Event TRB. Otherwise, it
+    /// returns the Event TRB, or an [`Err`] value with the raw TRB if this crate does not support
+    /// its type.
+    fn next(&mut self) -> Option<Self::Item> {
+        let raw = self.segments[self.dequeue.segment].read(self.dequeue.index);
+
+        if raw[3].get_bit(0) == self.cycle_state {
+            self.advance();
+            Some(Allowed::try_from(raw))
+        } else {
+            None
+        }
+    }
+}
+
+/// Event Ring Segment Table Entry.
+///
+/// Both the Interrupters and the Debug Capability refer to an Event Ring Segment Table, which is an
+/// array of this type. The address of the table is written to
+/// [`EventRingSegmentTableBaseAddressRegister`](crate::registers::runtime::EventRingSegmentTableBaseAddressRegister)
+/// or to [`EventRingSegmentTableBaseAddress`](crate::extended_capabilities::debug::EventRingSegmentTableBaseAddress),
+/// and the number of entries is written to the corresponding size register.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct EventRingSegmentTableEntry {
+    ring_segment_base_address: u64,
+    ring_segment_size: u16,
+    _rsvd0: u16,
+    _rsvd1: u32,
+}
+impl EventRingSegmentTableEntry {
+    /// The minimum number of TRBs an Event Ring segment must contain.
+    pub const MIN_RING_SEGMENT_SIZE: u16 = 16;
+    /// The maximum number of TRBs an Event Ring segment can contain.
+    pub const MAX_RING_SEGMENT_SIZE: u16 = 4096;
+
+    /// Creates a new entry which describes the segment of `size` TRBs starting at `base`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `base` is not 64-byte aligned, or if `size` is not within `16..=4096`.
+    #[must_use]
+    pub fn new(base: u64, size: u16) -> Self {
+        let mut e = Self {
+            ring_segment_base_address: 0,
+            ring_segment_size: Self::MIN_RING_SEGMENT_SIZE,
+            _rsvd0: 0,
+            _rsvd1: 0,
+        };
+        e.set_ring_segment_base_address(base);
+        e.set_ring_segment_size(size);
+        e
+    }
+
+    /// Creates a new entry which describes `segment`.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if the segment is not 64-byte aligned, or if the number of TRBs the
+    /// segment contains is not within `16..=4096`.
+    #[must_use]
+    pub fn from_segment(segment: &Segment) -> Self {
+        let size = u16::try_from(segment.number_of_trbs())
+            .ok()
+            .filter(|s| *s <= Self::MAX_RING_SEGMENT_SIZE)
+            .expect("An Event Ring segment must contain at most 4096 TRBs.");
+
+        Self::new(segment.phys_base(), size)
+    }
+
+    /// Writes the entries describing `segments` to the beginning of `table`, and returns the
+    /// number of the written entries, which is the value of the Event Ring Segment Table Size
+    /// register.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `table` is shorter than `segments`, or if one of the segments does
+    /// not meet the conditions described in [`EventRingSegmentTableEntry::from_segment`].
+    pub fn write_table(table: &mut [Self], segments: &[Segment]) -> u16 {
+        assert!(
+            table.len() >= segments.len(),
+            "The Event Ring Segment Table is too short."
+        );
+
+        for (e, s) in table.iter_mut().zip(segments) {
+            *e = Self::from_segment(s);
+        }
+
+        segments
+            .len()
+            .try_into()
+            .expect("The number of segments must fit in u16.")
+    }
+
+    /// Returns the total number of TRBs the segments described by `table` contain.
+    #[must_use]
+    pub fn total_trbs(table: &[Self]) -> usize {
+        table.iter().map(|e| usize::from(e.ring_segment_size)).sum()
+    }
+
+    /// Returns the value of the Ring Segment Base Address field.
+    #[must_use]
+    pub fn ring_segment_base_address(&self) -> u64 {
+        self.ring_segment_base_address
+    }
+
+    /// Sets the value of the Ring Segment Base Address field.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `base` is not 64-byte aligned.
+    pub fn set_ring_segment_base_address(&mut self, base: u64) -> &mut Self {
+        assert_eq!(
+            base % 64,
+            0,
+            "The Ring Segment Base Address must be 64-byte aligned."
+        );
+
+        self.ring_segment_base_address = base;
+        self
+    }
+
+    /// Returns the value of the Ring Segment Size field, which is the number of TRBs the segment
+    /// contains.
+    #[must_use]
+    pub fn ring_segment_size(&self) -> u16 {
+        self.ring_segment_size
+    }
+
+    /// Sets the value of the Ring Segment Size field, which is the number of TRBs the segment
+    /// contains.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `size` is not within `16..=4096`.
+    pub fn set_ring_segment_size(&mut self, size: u16) -> &mut Self {
+        assert!(
+            (Self::MIN_RING_SEGMENT_SIZE..=Self::MAX_RING_SEGMENT_SIZE).contains(&size),
+            "The Ring Segment Size must be within 16..=4096."
+        );
+
+        self.ring_segment_size = size;
+        self
+    }
+}
+
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Default, Debug)]
+struct Position {
+    segment: usize,
+    index: usize,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ring::trb::event::{CommandCompletion, PortStatusChange};
+    use crate::test_util::Memory;
+
+    fn put<const L: usize>(m: &mut Memory<L>, i: usize, trb: [u32; 4]) {
+        unsafe { ptr::write_volatile(ptr::addr_of_mut!(m.0[i]), trb) }
+    }
+
+    fn produce<const L: usize>(m: &mut Memory<L>, i: usize, cycle: bool) {
+        let mut t = PortStatusChange::new();
+        if cycle {
+            t.set_cycle_bit();
+        }
+        put(m, i, t.into_raw());
+    }
+
+    fn erst_entry<const L: usize>(erst: &Memory<L>, i: usize) -> (u64, u32) {
+        let e = erst.0[i];
+        (u64::from(e[1]) << 32 | u64::from(e[0]), e[2])
+    }
+
+    #[test]
+    fn erst_is_written() {
+        let mut erst = Memory::<2>::new();
+        let mut s0 = Memory::<16>::new();
+        let mut s1 = Memory::<32>::new();
+        let ring = unsafe {
+            EventRing::new(
+                erst.addr(),
+                erst.addr() as u64,
+                [s0.segment(), s1.segment()],
+            )
+        };
+
+        assert_eq!(erst_entry(&erst, 0), (s0.addr() as u64, 16));
+        assert_eq!(erst_entry(&erst, 1), (s1.addr() as u64, 32));
+        assert_eq!(s0.0[0], [0; 4]);
+        assert_eq!(ring.erst_size(), 2);
+    }
+
+    #[test]
+    fn dequeue_across_segments() {
+        let mut erst = Memory::<2>::new();
+        let mut s0 = Memory::<16>::new();
+        let mut s1 = Memory::<16>::new();
+        let mut ring = unsafe {
+            EventRing::new(
+                erst.addr(),
+                erst.addr() as u64,
+                [s0.segment(), s1.segment()],
+            )
+        };
+
+        assert_eq!(ring.next(), None);
+
+        for i in 0..16 {
+            produce(&mut s0, i, true);
+        }
+        produce(&mut s1, 0, true);
+        assert_eq!(ring.by_ref().count(), 17);
+        assert_eq!(ring.dequeue_pointer(), s1.addr() as u64 + 16);
+        assert_eq!(ring.dequeue_segment_index(), 1);
+
+        for i in 1..16 {
+            produce(&mut s1, i, true);
+        }
+        produce(&mut s0, 0, false);
+        assert_eq!(ring.by_ref().count(), 16);
+        assert!(!ring.cycle_state());
+
+        put(
+            &mut s0,
+            1,
+            CommandCompletion::new().set_cycle_bit().into_raw(),
+        );
+        assert_eq!(ring.by_ref().count(), 0);
+        put(&mut s0, 1, CommandCompletion::new().into_raw());
+        assert!(matches!(
+            ring.next(),
+            Some(Ok(Allowed::CommandCompletion(_)))
+        ));
+    }
+
+    #[test]
+    fn write_table_validates_segments() {
+        let mut s0 = Memory::<16>::new();
+        let mut s1 = Memory::<64>::new();
+        let mut table = [EventRingSegmentTableEntry::new(0, 16); 3];
+
+        let n = EventRingSegmentTableEntry::write_table(&mut table, &[s0.segment(), s1.segment()]);
+        assert_eq!(n, 2);
+        assert_eq!(table[1].ring_segment_base_address(), s1.addr() as u64);
+        assert_eq!(EventRingSegmentTableEntry::total_trbs(&table[..2]), 80);
+    }
+
+    #[test]
+    #[should_panic(expected = "The Ring Segment Size must be within 16..=4096.")]
+    fn too_small_segment_is_rejected() {
+        let _ = EventRingSegmentTableEntry::new(0x1000, 15);
+    }
+
+    #[test]
+    fn erdp_clears_event_handler_busy() {
+        let mut erst = Memory::<2>::new();
+        let mut s0 = Memory::<16>::new();
+        let mut s1 = Memory::<16>::new();
+        let mut ring = unsafe {
+            EventRing::new(
+                erst.addr(),
+                erst.addr() as u64,
+                [s0.segment(), s1.segment()],
+            )
+        };
+
+        for i in 0..16 {
+            produce(&mut s0, i, true);
+        }
+        produce(&mut s1, 0, true);
+        assert_eq!(ring.by_ref().count(), 17);
+
+        let erdp = ring.erdp();
+        assert_eq!(erdp.event_ring_dequeue_pointer(), s1.addr() as u64 + 16);
+        assert_eq!(erdp.dequeue_erst_segment_index(), 1);
+        assert!(erdp.event_handler_busy());
+    }
+}
@@ -1,3 +1,122 @@
 //! TRB Ring.
 
+use core::convert::TryFrom;
+use core::ptr;
+
+pub mod event;
+pub mod producer;
+pub mod tracker;
 pub mod trb;
+
+/// A segment of a TRB Ring.
+///
+/// A segment is a physically contiguous block of memory that contains TRBs. This struct does not
+/// own the memory. It only remembers where the memory is, so that the rings of this module can be
+/// used regardless of how the memory is allocated.
+#[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct Segment {
+    virt: usize,
+    phys: u64,
+    len: usize,
+}
+impl Segment {
+    /// Creates a new segment which contains `len` TRBs.
+    ///
+    /// # Safety
+    ///
+    /// `virt` must be the virtual address of a memory block that can hold `len` TRBs, and `phys`
+    /// must be the physical address of the same block. The block must stay accessible while a
+    /// ring using this segment exists, and it must be accessed only through that ring.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `virt` or `phys` is not 16-byte aligned, or if `len == 0`.
+    #[must_use]
+    pub unsafe fn new(virt: usize, phys: u64, len: usize) -> Self {
+        assert_eq!(virt % 16, 0, "The segment must be 16-byte aligned.");
+        assert_eq!(phys % 16, 0, "The segment must be 16-byte aligned.");
+        assert_ne!(len, 0, "The segment must contain at least one TRB.");
+
+        Self { virt, phys, len }
+    }
+
+    /// Returns the physical address of the first TRB of this segment.
+    #[must_use]
+    pub fn phys_base(&self) -> u64 {
+        self.phys
+    }
+
+    /// Returns the number of TRBs this segment can hold.
+    #[must_use]
+    pub fn number_of_trbs(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the physical address of the `i`th TRB.
+    fn trb_address(&self, i: usize) -> u64 {
+        debug_assert!(i < self.len);
+
+        self.phys + u64::try_from(i * trb::BYTES).unwrap()
+    }
+
+    /// Returns the index of the TRB located at `phys`, if this segment contains it.
+    fn index_of(&self, phys: u64) -> Option<usize> {
+        let offset = usize::try_from(phys.checked_sub(self.phys)?).ok()?;
+
+        if offset % trb::BYTES == 0 && offset / trb::BYTES < self.len {
+            Some(offset / trb::BYTES)
+        } else {
+            None
+        }
+    }
+
+    /// Reads the `i`th TRB.
+    ///
+    /// The fourth dword, which contains the Cycle bit, is read first so that the rest of the TRB
+    /// is never older than the Cycle bit.
+    fn read(&self, i: usize) -> [u32; 4] {
+        let p = self.dword_ptr(i);
+        let mut trb = [0; 4];
+
+        // SAFETY: `Segment::new` ensures that the memory is accessible.
+        unsafe {
+            trb[3] = ptr::read_volatile(p.add(3));
+            core::sync::atomic::fence(core::sync::atomic::Ordering::Acquire);
+            for (j, d) in trb.iter_mut().enumerate().take(3) {
+                *d = ptr::read_volatile(p.add(j));
+            }
+        }
+
+        trb
+    }
+
+    /// Writes `trb` to the `i`th slot.
+    ///
+    /// The fourth dword, which contains the Cycle bit, is written last so that the xHC never
+    /// sees a partially written TRB with the correct Cycle bit.
+    fn write(&self, i: usize, trb: [u32; 4]) {
+        let p = self.dword_ptr(i);
+
+        // SAFETY: `Segment::new` ensures that the memory is accessible.
+        unsafe {
+            for (j, d) in trb.iter().enumerate().take(3) {
+                ptr::write_volatile(p.add(j), *d);
+            }
+            core::sync::atomic::fence(core::sync::atomic::Ordering::Release);
+            ptr::write_volatile(p.add(3), trb[3]);
+        }
+    }
+
+    /// Fills the whole segment with 0.
+    fn clear(&self) {
+        for i in 0..self.len {
+            self.write(i, [0; 4]);
+        }
+    }
+
+    fn dword_ptr(&self, i: usize) -> *mut u32 {
+        assert!(i < self.len, "The index is out of the segment.");
+
+        (self.virt + i * trb::BYTES) as *mut u32
+    }
+}
@@ -0,0 +1,360 @@
+//! The producer side of TRB Rings.
+//!
+//! Software is the producer of the Command Ring and the Transfer Rings. [`ProducerRing`] manages
+//! the Enqueue Pointer and the Producer Cycle State of such a ring, and inserts Link TRBs at the
+//! end of each segment.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use xhci::ring::{producer::ProducerRing, trb::command, Segment};
+//!
+//! # let (virt, phys) = (0x1000, 0x1000);
+//! let segment = unsafe { Segment::new(virt, phys, 256) };
+//! let mut ring = ProducerRing::<command::Allowed, 1>::new([segment]);
+//!
+//! // Write `ring.enqueue_pointer()` and `ring.cycle_state()` to the CRCR register before
+//! // enqueueing the first TRB.
+//!
+//! let addr = ring
+//!     .enqueue(command::Allowed::Noop(command::Noop::new()))
+//!     .expect("The Command Ring is full.");
+//!
+//! // Ring the Host Controller doorbell, and wait for the Command Completion Event whose Command
+//! // TRB Pointer field is `addr`.
+//! # let _ = addr;
+//! ```
+
+use super::trb::Link;
+use super::Segment;
+use bit_field::BitField;
+use core::marker::PhantomData;
+
+/// A TRB Ring whose producer is software, namely the Command Ring or a Transfer Ring.
+///
+/// `T` is either [`command::Allowed`](super::trb::command::Allowed) or
+/// [`transfer::Allowed`](super::trb::transfer::Allowed), and `N` is the number of segments.
+///
+/// The last TRB of each segment is reserved for a Link TRB, which this struct writes when the
+/// Enqueue Pointer passes it. The Link TRB of the last segment points to the first segment and has
+/// the Toggle Cycle bit set.
+///
+/// The ring is regarded as full if enqueueing one more TRB makes the Enqueue Pointer equal to the
+/// Dequeue Pointer. Therefore, the ring can hold one TRB less than its usable TRB slots.
+#[derive(Debug)]
+pub struct ProducerRing<T, const N: usize> {
+    segments: [Segment; N],
+    enqueue: Position,
+    dequeue: Position,
+    cycle_state: bool,
+    _marker: PhantomData<fn(T)>,
+}
+impl<T, const N: usize> ProducerRing<T, N>
+where
+    T: Into<[u32; 4]> + From<Link>,
+{
+    /// Creates a new ring over `segments`.
+    ///
+    /// This method fills all segments with 0. The Producer Cycle State is initialized to 1.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `N == 0`, if a segment contains less than 2 TRBs, or if the ring
+    /// cannot hold any TRB.
+    #[must_use]
+    pub fn new(segments: [Segment; N]) -> Self {
+        assert_ne!(N, 0, "A ring must have at least one segment.");
+        assert!(
+            segments.iter().all(|s| s.number_of_trbs() >= 2),
+            "Each segment must be able to contain at least one TRB and a Link TRB."
+        );
+        assert!(
+            segments
+                .iter()
+                .map(|s| s.number_of_trbs() - 1)
+                .sum::<usize>()
+                >= 2,
+            "The ring must have at least two usable TRB slots."
+        );
+
+        for s in &segments {
+            s.clear();
+        }
+
+        let head = Position::default();
+        Self {
+            segments,
+            enqueue: head,
+            dequeue: head,
+            cycle_state: true,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Enqueues `trb` and returns the physical address of it.
+    ///
+    /// This method sets the Cycle bit of `trb` to the Producer Cycle State. The returned address
+    /// is the one the xHC reports in the Command TRB Pointer field of a Command Completion Event or
+    /// in the TRB Pointer field of a Transfer Event.
+    ///
+    /// If the Enqueue Pointer reaches the end of a segment, this method also writes a Link TRB
+    /// there. The Chain bit of the Link TRB is copied from `trb` so that a TD can span multiple
+    /// segments.
+    ///
+    /// # Errors
+    ///
+    /// This method returns a [`Full`] error if the ring has no space for `trb`.
+    pub fn enqueue(&mut self, trb: T) -> Result<u64, Full> {
+        let next = self.next(self.enqueue);
+        if next == self.dequeue {
+            return Err(Full);
+        }
+
+        let mut raw: [u32; 4] = trb.into();
+        raw[3].set_bit(0, self.cycle_state);
+
+        let segment = &self.segments[self.enqueue.segment];
+        segment.write(self.enqueue.index, raw);
+        let addr = segment.trb_address(self.enqueue.index);
+
+        if next.index == 0 {
+            self.write_link(raw[3].get_bit(4));
+        }
+
+        self.enqueue = next;
+        Ok(addr)
+    }
+
+    /// Notifies the ring that the xHC has consumed the TRB at `trb_pointer` and all TRBs before
+    /// it.
+    ///
+    /// Pass the Command TRB Pointer field of a Command Completion Event, or the TRB Pointer field
+    /// of a Transfer Event which does not have the Event Data bit set. The slots of the consumed
+    /// TRBs become available for [`ProducerRing::enqueue`].
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `trb_pointer` does not point to a TRB slot of this ring.
+    pub fn update_dequeue_pointer(&mut self, trb_pointer: u64) {
+        let consumed = self
+            .position_of(trb_pointer)
+            .expect("The TRB Pointer does not point to this ring.");
+
+        self.dequeue = self.next(consumed);
+    }
+
+    /// Returns the physical address the next TRB will be written to.
+    ///
+    /// Before enqueueing any TRB, this is the address to write to the Command Ring Pointer field of
+    /// the CRCR register or to the TR Dequeue Pointer field of an Endpoint Context.
+    #[must_use]
+    pub fn enqueue_pointer(&self) -> u64 {
+        self.segments[self.enqueue.segment].trb_address(self.enqueue.index)
+    }
+
+    /// Returns the Producer Cycle State.
+    ///
+    /// Before enqueueing any TRB, this is the value of the Ring Cycle State bit of the CRCR
+    /// register or of the Dequeue Cycle State bit of an Endpoint Context.
+    #[must_use]
+    pub fn cycle_state(&self) -> bool {
+        self.cycle_state
+    }
+
+    /// Returns `true` if the ring has no space for another TRB.
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        self.next(self.enqueue) == self.dequeue
+    }
+
+    /// Returns the number of TRBs which can be enqueued before the ring becomes full.
+    #[must_use]
+    pub fn free_trbs(&self) -> usize {
+        let usable = self.usable_trbs_before(N);
+        let enqueue = self.offset_of(self.enqueue);
+        let dequeue = self.offset_of(self.dequeue);
+
+        (dequeue + usable - enqueue - 1) % usable
+    }
+
+    /// Returns `true` if the xHC has consumed all enqueued TRBs.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.enqueue == self.dequeue
+    }
+
+    fn write_link(&mut self, chain: bool) {
+        let current = self.enqueue.segment;
+        let toggle = current == N - 1;
+        let next = &self.segments[(current + 1) % N];
+
+        let mut link = Link::new();
+        link.set_ring_segment_pointer(next.phys_base());
+        if toggle {
+            link.set_toggle_cycle();
+        }
+        if chain {
+            link.set_chain_bit();
+        }
+        if self.cycle_state {
+            link.set_cycle_bit();
+        }
+
+        let segment = &self.segments[current];
+        segment.write(segment.number_of_trbs() - 1, link.into_raw());
+
+        if toggle {
+            self.cycle_state = !self.cycle_state;
+        }
+    }
+
+    fn next(&self, p: Position) -> Position {
+        if p.index + 2 < self.segments[p.segment].number_of_trbs() {
+            Position {
+                segment: p.segment,
+                index: p.index + 1,
+            }
+        } else {
+            Position {
+                segment: (p.segment + 1) % N,
+                index: 0,
+            }
+        }
+    }
+
+    /// Returns the number of TRB slots before `p`, excluding the Link TRBs.
+    fn offset_of(&self, p: Position) -> usize {
+        self.usable_trbs_before(p.segment) + p.index
+    }
+
+    /// Returns the number of TRB slots of the first `segment` segments, excluding the Link TRBs.
+    fn usable_trbs_before(&self, segment: usize) -> usize {
+        self.segments[..segment]
+            .iter()
+            .map(|s| s.number_of_trbs() - 1)
+            .sum()
+    }
+
+    fn position_of(&self, phys: u64) -> Option<Position> {
+        self.segments.iter().enumerate().find_map(|(segment, s)| {
+            let index = s.index_of(phys)?;
+            (index + 1 < s.number_of_trbs()).then_some(Position { segment, index })
+        })
+    }
+}
+
+/// An error returned when a ring has no space for another TRB.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Default, Debug)]
+pub struct Full;
+
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Default, Debug)]
+struct Position {
+    segment: usize,
+    index: usize,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ring::trb::{command, transfer};
+    use crate::test_util::Memory;
+
+    fn noop() -> command::Allowed {
+        command::Allowed::Noop(command::Noop::new())
+    }
+
+    #[test]
+    fn enqueue_sets_cycle_bit_and_returns_address() {
+        let mut m = Memory::<4>::new();
+        let mut ring = ProducerRing::<command::Allowed, 1>::new([m.segment()]);
+
+        assert_eq!(ring.enqueue_pointer(), m.base());
+        assert_eq!(ring.enqueue(noop()), Ok(m.base()));
+        assert_eq!(ring.enqueue(noop()), Ok(m.base() + 16));
+
+        let trb = command::Allowed::try_from(m.0[0]).unwrap();
+        assert!(trb.cycle_bit());
+        assert_eq!(m.0[2], [0; 4]);
+    }
+
+    #[test]
+    fn link_trb_toggles_cycle_state() {
+        let mut m0 = Memory::<3>::new();
+        let mut m1 = Memory::<3>::new();
+        let mut ring = ProducerRing::<command::Allowed, 2>::new([m0.segment(), m1.segment()]);
+
+        for i in 0..3 {
+            let a = ring.enqueue(noop()).unwrap();
+            ring.update_dequeue_pointer(a);
+            assert!(ring.is_empty(), "{}", i);
+        }
+
+        let link = Link::try_from(m0.0[2]).unwrap();
+        assert_eq!(link.ring_segment_pointer(), m1.base());
+        assert!(!link.toggle_cycle());
+        assert!(link.cycle_bit());
+
+        ring.enqueue(noop()).unwrap();
+        let link = Link::try_from(m1.0[2]).unwrap();
+        assert_eq!(link.ring_segment_pointer(), m0.base());
+        assert!(link.toggle_cycle());
+        assert!(link.cycle_bit());
+
+        assert!(!ring.cycle_state());
+        assert_eq!(ring.enqueue_pointer(), m0.base());
+    }
+
+    #[test]
+    fn chain_bit_is_copied_to_link_trb() {
+        let mut m = Memory::<3>::new();
+        let mut ring = ProducerRing::<transfer::Allowed, 1>::new([m.segment()]);
+
+        let a = ring
+            .enqueue(transfer::Allowed::Noop(transfer::Noop::new()))
+            .unwrap();
+        ring.update_dequeue_pointer(a);
+        ring.enqueue(transfer::Allowed::Normal(
+            *transfer::Normal::new().set_chain_bit(),
+        ))
+        .unwrap();
+
+        assert!(Link::try_from(m.0[2]).unwrap().chain_bit());
+    }
+
+    #[test]
+    fn full_ring_rejects_trbs() {
+        let mut m = Memory::<4>::new();
+        let mut ring = ProducerRing::<command::Allowed, 1>::new([m.segment()]);
+
+        assert_eq!(ring.free_trbs(), 2);
+        let first = ring.enqueue(noop()).unwrap();
+        ring.enqueue(noop()).unwrap();
+        assert!(ring.is_full());
+        assert_eq!(ring.free_trbs(), 0);
+        assert_eq!(ring.enqueue(noop()), Err(Full));
+
+        ring.update_dequeue_pointer(first);
+        assert!(ring.enqueue(noop()).is_ok());
+    }
+
+    #[test]
+    fn free_trbs_skips_link_trbs() {
+        let mut m0 = Memory::<3>::new();
+        let mut m1 = Memory::<4>::new();
+        let mut ring = ProducerRing::<command::Allowed, 2>::new([m0.segment(), m1.segment()]);
+
+        assert_eq!(ring.free_trbs(), 4);
+        let mut last = 0;
+        for n in (0..4).rev() {
+            last = ring.enqueue(noop()).unwrap();
+            assert_eq!(ring.free_trbs(), n);
+        }
+
+        // The Dequeue Pointer is behind the Enqueue Pointer after wrapping around.
+        ring.update_dequeue_pointer(m0.base());
+        assert_eq!(ring.free_trbs(), 1);
+        ring.enqueue(noop()).unwrap();
+        ring.update_dequeue_pointer(last);
+        assert_eq!(ring.free_trbs(), 3);
+    }
+}
@@ -0,0 +1,752 @@
+//! Correlation of events with the TRBs which caused them.
+//!
+//! [`CommandTracker`] records the Command TRBs enqueued to the Command Ring, and resolves them
+//! when the matching Command Completion Events arrive. Each tracked command is represented by a
+//! [`CommandFuture`], so that an async executor can wait for the completion without polling the
+//! Event Ring by itself.
+//!
+//! [`TransferTracker`] records the TDs enqueued to a Transfer Ring, and converts Transfer Events
+//! into the results of the TDs.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use xhci::ring::producer::ProducerRing;
+//! use xhci::ring::tracker::CommandTracker;
+//! use xhci::ring::trb::{command, event};
+//!
+//! # async fn f(ring: &mut ProducerRing<command::Allowed, 1>) {
+//! let tracker = CommandTracker::<8>::new();
+//!
+//! let completion = tracker
+//!     .enqueue(ring, command::Allowed::EnableSlot(command::EnableSlot::new()))
+//!     .expect("Failed to enqueue the command.");
+//! // Ring the Host Controller doorbell.
+//!
+//! // In the event handler:
+//! # let e: event::CommandCompletion = unimplemented!();
+//! ring.update_dequeue_pointer(e.command_trb_pointer());
+//! tracker.complete(e);
+//!
+//! let slot_id = completion.await.expect("Enable Slot Command failed.").slot_id();
+//! # }
+//! ```
+
+use super::producer::ProducerRing;
+use super::trb::event::{CommandCompletion, CompletionCode, TransferEvent};
+use super::trb::{command, transfer};
+use core::cell::RefCell;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+/// A tracker of the commands waiting for their completions.
+///
+/// `N` is the maximum number of commands which can be tracked at the same time. The tracker does
+/// not allocate memory.
+///
+/// The tracker is not [`Sync`]. Use it from the executor thread only, or wrap it with a lock.
+#[derive(Debug)]
+pub struct CommandTracker<const N: usize> {
+    slots: RefCell<[Slot; N]>,
+}
+impl<const N: usize> CommandTracker<N> {
+    /// Creates a tracker which tracks no commands.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            slots: RefCell::new(core::array::from_fn(|_| Slot::Free)),
+        }
+    }
+
+    /// Enqueues `command` to `ring`, and starts tracking it.
+    ///
+    /// Ring the Host Controller doorbell after calling this method.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the tracker has no free slot or if the ring is full. The
+    /// command is not enqueued in either case.
+    pub fn enqueue<const S: usize>(
+        &self,
+        ring: &mut ProducerRing<command::Allowed, S>,
+        command: command::Allowed,
+    ) -> Result<CommandFuture<'_, N>, EnqueueError> {
+        let index = self.free_slot().ok_or(EnqueueError::TrackerFull)?;
+        let addr = ring.enqueue(command).map_err(|_| EnqueueError::RingFull)?;
+
+        Ok(self.track(index, addr))
+    }
+
+    /// Starts tracking the Command TRB located at `addr`, which is the value
+    /// [`ProducerRing::enqueue`] returned.
+    ///
+    /// # Errors
+    ///
+    /// This method returns [`EnqueueError::TrackerFull`] if the tracker has no free slot.
+    pub fn register(&self, addr: u64) -> Result<CommandFuture<'_, N>, EnqueueError> {
+        let index = self.free_slot().ok_or(EnqueueError::TrackerFull)?;
+
+        Ok(self.track(index, addr))
+    }
+
+    /// Resolves the command which `event` reports the completion of, and wakes the task waiting
+    /// for it.
+    ///
+    /// This method returns `false` if no tracked command matches the Command TRB Pointer field of
+    /// `event`. Updating the Dequeue Pointer of the Command Ring is the caller's responsibility.
+    pub fn complete(&self, event: CommandCompletion) -> bool {
+        let addr = event.command_trb_pointer();
+        let mut slots = self.slots.borrow_mut();
+
+        let Some(slot) = slots.iter_mut().find(|s| s.addr() == Some(addr)) else {
+            return false;
+        };
+
+        match core::mem::replace(slot, Slot::Free) {
+            Slot::Pending { waker, .. } => {
+                *slot = Slot::Completed(event);
+                drop(slots);
+
+                if let Some(w) = waker {
+                    w.wake();
+                }
+            }
+            Slot::Abandoned(_) | Slot::Free | Slot::Completed(_) => {}
+        }
+
+        true
+    }
+
+    /// Returns the number of commands waiting for their completions.
+    #[must_use]
+    pub fn pending(&self) -> usize {
+        self.slots
+            .borrow()
+            .iter()
+            .filter(|s| s.addr().is_some())
+            .count()
+    }
+
+    fn free_slot(&self) -> Option<usize> {
+        self.slots
+            .borrow()
+            .iter()
+            .position(|s| matches!(s, Slot::Free))
+    }
+
+    fn track(&self, index: usize, addr: u64) -> CommandFuture<'_, N> {
+        self.slots.borrow_mut()[index] = Slot::Pending { addr, waker: None };
+
+        CommandFuture {
+            tracker: self,
+            index,
+            done: false,
+        }
+    }
+}
+impl<const N: usize> Default for CommandTracker<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A future which resolves when the tracked command completes.
+///
+/// The output is the Command Completion Event if the Completion Code is Success, or the
+/// Completion Code otherwise. A Completion Code this crate does not know is reported as
+/// [`CompletionCode::UndefinedError`].
+///
+/// Dropping this future before the completion stops tracking the command. The tracker discards
+/// the completion when it arrives.
+#[derive(Debug)]
+pub struct CommandFuture<'a, const N: usize> {
+    tracker: &'a CommandTracker<N>,
+    index: usize,
+    done: bool,
+}
+impl<const N: usize> CommandFuture<'_, N> {
+    /// Returns the physical address of the tracked Command TRB.
+    #[must_use]
+    pub fn command_trb_pointer(&self) -> Option<u64> {
+        if self.done {
+            None
+        } else {
+            self.tracker.slots.borrow()[self.index].addr_or_completed()
+        }
+    }
+}
+impl<const N: usize> Future for CommandFuture<'_, N> {
+    type Output = Result<CommandCompletion, CompletionCode>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        assert!(!self.done, "The future is polled after completion.");
+
+        let mut slots = self.tracker.slots.borrow_mut();
+        let slot = &mut slots[self.index];
+
+        match slot {
+            Slot::Completed(e) => {
+                let e = *e;
+                *slot = Slot::Free;
+                drop(slots);
+                self.done = true;
+
+                Poll::Ready(match e.completion_code() {
+                    Ok(CompletionCode::Success) => Ok(e),
+                    Ok(c) => Err(c),
+                    Err(_) => Err(CompletionCode::UndefinedError),
+                })
+            }
+            Slot::Pending { waker, .. } => {
+                if !waker.as_ref().is_some_and(|w| w.will_wake(cx.waker())) {
+                    *waker = Some(cx.waker().clone());
+                }
+
+                Poll::Pending
+            }
+            Slot::Free | Slot::Abandoned(_) => unreachable!("The slot is not tracked."),
+        }
+    }
+}
+impl<const N: usize> Drop for CommandFuture<'_, N> {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+
+        let mut slots = self.tracker.slots.borrow_mut();
+        let slot = &mut slots[self.index];
+
+        *slot = match slot {
+            Slot::Pending { addr, .. } => Slot::Abandoned(*addr),
+            _ => Slot::Free,
+        };
+    }
+}
+
+/// An error returned when a command cannot be tracked.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub enum EnqueueError {
+    /// The tracker has no free slot.
+    TrackerFull,
+    /// The Command Ring is full.
+    RingFull,
+}
+
+#[derive(Debug)]
+enum Slot {
+    Free,
+    Pending { addr: u64, waker: Option<Waker> },
+    Completed(CommandCompletion),
+    Abandoned(u64),
+}
+impl Slot {
+    /// Returns the address of the Command TRB waiting for its completion.
+    fn addr(&self) -> Option<u64> {
+        match self {
+            Self::Pending { addr, .. } | Self::Abandoned(addr) => Some(*addr),
+            Self::Free | Self::Completed(_) => None,
+        }
+    }
+
+    fn addr_or_completed(&self) -> Option<u64> {
+        match self {
+            Self::Completed(e) => Some(e.command_trb_pointer()),
+            s => s.addr(),
+        }
+    }
+}
+
+/// A tracker of the TDs enqueued to a Transfer Ring.
+///
+/// `N` is the maximum number of TRBs which can be tracked at the same time. The tracker does not
+/// allocate memory.
+///
+/// Set the Interrupt On Completion bit of the last TRB of each TD. The xHC does not report the
+/// completion of a TD without it. When an event for a later TD arrives, the tracker drops such a
+/// TD without returning its completion.
+///
+/// Streams are not supported. Use a tracker for each Transfer Ring.
+#[derive(Debug)]
+pub struct TransferTracker<const N: usize> {
+    trbs: [TrbRecord; N],
+    head: usize,
+    len: usize,
+    next_id: u32,
+    short_packet: Option<u32>,
+}
+impl<const N: usize> TransferTracker<N> {
+    /// Creates a tracker which tracks no TDs.
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            trbs: [TrbRecord::default(); N],
+            head: 0,
+            len: 0,
+            next_id: 0,
+            short_packet: None,
+        }
+    }
+
+    /// Enqueues the TRBs of a TD to `ring`, and starts tracking the TD.
+    ///
+    /// Ring the doorbell of the endpoint after calling this method.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if `td` is empty, or if the tracker or the ring does not have
+    /// space for all TRBs of `td`. No TRB is enqueued in these cases.
+    pub fn submit<const S: usize>(
+        &mut self,
+        ring: &mut ProducerRing<transfer::Allowed, S>,
+        td: &[transfer::Allowed],
+    ) -> Result<TdId, SubmitError> {
+        if td.is_empty() {
+            return Err(SubmitError::Empty);
+        }
+        if N - self.len < td.len() {
+            return Err(SubmitError::TrackerFull);
+        }
+        if ring.free_trbs() < td.len() {
+            return Err(SubmitError::RingFull);
+        }
+
+        let id = TdId(self.next_id);
+        self.next_id = self.next_id.wrapping_add(1);
+
+        for (i, trb) in td.iter().enumerate() {
+            let addr = ring.enqueue(*trb).expect("The ring has enough space.");
+            let (length, event_data) = match trb {
+                transfer::Allowed::Normal(t) => (t.trb_transfer_length(), None),
+                transfer::Allowed::DataStage(t) => (t.trb_transfer_length(), None),
+                transfer::Allowed::Isoch(t) => (t.trb_transfer_length(), None),
+                transfer::Allowed::EventData(t) => (0, Some(t.event_data())),
+                _ => (0, None),
+            };
+
+            self.push(TrbRecord {
+                id,
+                addr,
+                length,
+                event_data,
+                last: i + 1 == td.len(),
+                interrupt_on_completion: trb.interrupt_on_completion(),
+            });
+        }
+
+        Ok(id)
+    }
+
+    /// Converts a Transfer Event into the result of a TD.
+    ///
+    /// This method returns [`None`] if the event does not complete a tracked TD, for example if
+    /// the event reports a short packet in the middle of a TD whose last TRB will generate another
+    /// event. When a TD completes, this method updates the Dequeue Pointer of `ring`.
+    ///
+    /// The TDs enqueued before the TD the event is for are dropped without their completions.
+    pub fn handle<const S: usize>(
+        &mut self,
+        ring: &mut ProducerRing<transfer::Allowed, S>,
+        event: &TransferEvent,
+    ) -> Option<TdCompletion> {
+        let code = event.completion_code();
+
+        let (i, actual_length, event_data) = if event.event_data() {
+            let value = event.trb_pointer();
+            let i = self.find(|r| r.event_data == Some(value))?;
+
+            (i, event.trb_transfer_length(), Some(value))
+        } else {
+            let i = self.find(|r| r.addr == event.trb_pointer())?;
+            let r = self.get(i);
+            let transferred = r.length.saturating_sub(event.trb_transfer_length());
+
+            (i, self.requested_before(i) + transferred, None)
+        };
+
+        let i = self.discard_tds_before(i);
+        let record = self.get(i);
+
+        let last_ioc = self.last_of_head_td().interrupt_on_completion;
+        let (actual_length, completion_code) = match (record.last, code) {
+            (true, Ok(CompletionCode::Success | CompletionCode::ShortPacket)) => {
+                match self.short_packet.take() {
+                    Some(l) => (l, Ok(CompletionCode::ShortPacket)),
+                    None => (actual_length, code),
+                }
+            }
+            (false, Ok(CompletionCode::Success)) => return None,
+            (false, Ok(CompletionCode::ShortPacket)) if last_ioc => {
+                self.short_packet = Some(actual_length);
+                return None;
+            }
+            (_, c) => (actual_length, c),
+        };
+
+        let last = self.pop_head_td();
+        ring.update_dequeue_pointer(last.addr);
+
+        Some(TdCompletion {
+            id: record.id,
+            actual_length,
+            completion_code,
+            event_data,
+        })
+    }
+
+    /// Returns the number of TRBs of the TDs which have not completed.
+    #[must_use]
+    pub fn pending_trbs(&self) -> usize {
+        self.len
+    }
+
+    fn push(&mut self, r: TrbRecord) {
+        self.trbs[(self.head + self.len) % N] = r;
+        self.len += 1;
+    }
+
+    /// Returns the `i`th record counted from the oldest one.
+    fn get(&self, i: usize) -> TrbRecord {
+        debug_assert!(i < self.len);
+
+        self.trbs[(self.head + i) % N]
+    }
+
+    fn find(&self, f: impl Fn(&TrbRecord) -> bool) -> Option<usize> {
+        (0..self.len).find(|&i| f(&self.get(i)))
+    }
+
+    /// Returns the sum of the lengths of the TRBs before the `i`th record in the same TD.
+    fn requested_before(&self, i: usize) -> u32 {
+        let id = self.get(i).id;
+
+        (0..i)
+            .map(|j| self.get(j))
+            .filter(|r| r.id == id)
+            .map(|r| r.length)
+            .sum()
+    }
+
+    /// Removes the records of the TDs before the one of the `i`th record, and returns the new
+    /// index of the record. The xHC has processed these TDs, but it does not report their
+    /// completions.
+    fn discard_tds_before(&mut self, i: usize) -> usize {
+        let id = self.get(i).id;
+        let len = self.len;
+
+        while self.get(0).id != id {
+            self.pop_head_td();
+        }
+
+        i - (len - self.len)
+    }
+
+    fn last_of_head_td(&self) -> TrbRecord {
+        let i = self.find(|r| r.last).expect("A TD must have the last TRB.");
+        self.get(i)
+    }
+
+    /// Removes the records of the oldest TD, and returns its last record.
+    fn pop_head_td(&mut self) -> TrbRecord {
+        self.short_packet = None;
+
+        loop {
+            let r = self.get(0);
+            self.head = (self.head + 1) % N;
+            self.len -= 1;
+
+            if r.last {
+                return r;
+            }
+        }
+    }
+}
+impl<const N: usize> Default for TransferTracker<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The identifier of a TD returned by [`TransferTracker::submit`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub struct TdId(u32);
+
+/// The result of a TD.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub struct TdCompletion {
+    id: TdId,
+    actual_length: u32,
+    completion_code: Result<CompletionCode, u8>,
+    event_data: Option<u64>,
+}
+impl TdCompletion {
+    /// Returns the identifier of the TD.
+    #[must_use]
+    pub fn id(&self) -> TdId {
+        self.id
+    }
+
+    /// Returns the number of bytes transferred by the TD.
+    ///
+    /// If the TD completed with an error, this is the number of bytes transferred before the
+    /// error.
+    #[must_use]
+    pub fn actual_length(&self) -> u32 {
+        self.actual_length
+    }
+
+    /// Returns the Completion Code.
+    ///
+    /// The code is [`CompletionCode::ShortPacket`] if a short packet occurred in the TD, even if the
+    /// last TRB of the TD completed successfully.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an [`Err`] value with the Completion Code that is either reserved or not
+    /// implemented by this crate.
+    pub fn completion_code(&self) -> Result<CompletionCode, u8> {
+        self.completion_code
+    }
+
+    /// Returns the Event Data field of the Event Data TRB which generated the event, or [`None`] if
+    /// the event was generated by another TRB.
+    ///
+    /// If this method returns a value, [`TdCompletion::actual_length`] is the Event Data Transfer
+    /// Length the xHC reported.
+    #[must_use]
+    pub fn event_data(&self) -> Option<u64> {
+        self.event_data
+    }
+}
+
+/// An error returned when a TD cannot be submitted.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub enum SubmitError {
+    /// The TD contains no TRB.
+    Empty,
+    /// The tracker does not have space for the TD.
+    TrackerFull,
+    /// The Transfer Ring does not have space for the TD.
+    RingFull,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct TrbRecord {
+    id: TdId,
+    addr: u64,
+    length: u32,
+    event_data: Option<u64>,
+    last: bool,
+    interrupt_on_completion: bool,
+}
+impl Default for TrbRecord {
+    fn default() -> Self {
+        Self {
+            id: TdId(0),
+            addr: 0,
+            length: 0,
+            event_data: None,
+            last: false,
+            interrupt_on_completion: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::Memory;
+    use core::convert::TryFrom;
+    use core::pin::pin;
+
+    fn completion(addr: u64, code: u8) -> CommandCompletion {
+        let raw = [
+            u32::try_from(addr & 0xffff_ffff).unwrap(),
+            u32::try_from(addr >> 32).unwrap(),
+            u32::from(code) << 24,
+            (33 << 10) | (1 << 24),
+        ];
+
+        CommandCompletion::try_from(raw).unwrap()
+    }
+
+    #[test]
+    fn completion_resolves_matching_future() {
+        let tracker = CommandTracker::<2>::new();
+        let mut cx = Context::from_waker(Waker::noop());
+
+        let mut a = pin!(tracker.register(0x1000).unwrap());
+        let mut b = pin!(tracker.register(0x1010).unwrap());
+        assert!(tracker.register(0x1020).is_err());
+
+        assert!(a.as_mut().poll(&mut cx).is_pending());
+        assert!(tracker.complete(completion(0x1010, 5)));
+        assert!(!tracker.complete(completion(0x2000, 1)));
+
+        assert_eq!(
+            b.as_mut().poll(&mut cx),
+            Poll::Ready(Err(CompletionCode::TrbError))
+        );
+        assert_eq!(tracker.pending(), 1);
+
+        tracker.complete(completion(0x1000, 1));
+        let Poll::Ready(Ok(e)) = a.as_mut().poll(&mut cx) else {
+            panic!()
+        };
+        assert_eq!(e.slot_id(), 1);
+    }
+
+    #[test]
+    fn commands_are_enqueued_across_segments() {
+        let mut m0 = Memory::<3>::new();
+        let mut m1 = Memory::<3>::new();
+        let mut ring = ProducerRing::<command::Allowed, 2>::new([m0.segment(), m1.segment()]);
+        let tracker = CommandTracker::<3>::new();
+        let noop = || command::Allowed::Noop(command::Noop::new());
+
+        for _ in 0..3 {
+            drop(tracker.enqueue(&mut ring, noop()).unwrap());
+        }
+        assert_eq!(tracker.pending(), 3);
+
+        // The third command follows the Link TRB of the first segment.
+        assert!(tracker.complete(completion(m1.base(), 1)));
+        assert_eq!(tracker.pending(), 2);
+    }
+
+    #[test]
+    fn dropped_future_discards_completion() {
+        let tracker = CommandTracker::<1>::new();
+
+        drop(tracker.register(0x1000).unwrap());
+        assert_eq!(tracker.pending(), 1);
+
+        assert!(tracker.complete(completion(0x1000, 1)));
+        assert_eq!(tracker.pending(), 0);
+        assert!(tracker.register(0x1010).is_ok());
+    }
+
+    fn ring(m: &mut Memory<16>) -> ProducerRing<transfer::Allowed, 1> {
+        ProducerRing::new([m.segment()])
+    }
+
+    fn normal(length: u32, last: bool) -> transfer::Allowed {
+        let mut n = transfer::Normal::new();
+        n.set_trb_transfer_length(length)
+            .set_interrupt_on_short_packet();
+        if last {
+            n.set_interrupt_on_completion();
+        } else {
+            n.set_chain_bit();
+        }
+
+        transfer::Allowed::Normal(n)
+    }
+
+    fn raw_transfer_event(pointer: u64, length: u32, code: u8) -> [u32; 4] {
+        [
+            u32::try_from(pointer & 0xffff_ffff).unwrap(),
+            u32::try_from(pointer >> 32).unwrap(),
+            length | (u32::from(code) << 24),
+            (32 << 10) | (1 << 16) | (1 << 24),
+        ]
+    }
+
+    fn transfer_event(pointer: u64, length: u32, code: u8) -> TransferEvent {
+        TransferEvent::try_from(raw_transfer_event(pointer, length, code)).unwrap()
+    }
+
+    #[test]
+    fn chained_td_reports_total_length() {
+        let mut m = Memory::new();
+        let mut ring = ring(&mut m);
+        let mut tracker = TransferTracker::<8>::new();
+
+        let base = ring.enqueue_pointer();
+        let id = tracker
+            .submit(&mut ring, &[normal(512, false), normal(512, true)])
+            .unwrap();
+
+        let c = tracker
+            .handle(&mut ring, &transfer_event(base + 16, 0, 1))
+            .unwrap();
+        assert_eq!(c.id(), id);
+        assert_eq!(c.actual_length(), 1024);
+        assert_eq!(c.completion_code(), Ok(CompletionCode::Success));
+        assert!(ring.is_empty());
+        assert_eq!(tracker.pending_trbs(), 0);
+    }
+
+    #[test]
+    fn short_packet_in_the_middle_of_td() {
+        let mut m = Memory::new();
+        let mut ring = ring(&mut m);
+        let mut tracker = TransferTracker::<8>::new();
+
+        let base = ring.enqueue_pointer();
+        tracker
+            .submit(&mut ring, &[normal(512, false), normal(512, true)])
+            .unwrap();
+
+        assert!(tracker
+            .handle(&mut ring, &transfer_event(base, 100, 13))
+            .is_none());
+
+        let c = tracker
+            .handle(&mut ring, &transfer_event(base + 16, 512, 13))
+            .unwrap();
+        assert_eq!(c.actual_length(), 412);
+        assert_eq!(c.completion_code(), Ok(CompletionCode::ShortPacket));
+    }
+
+    #[test]
+    fn event_data_trb_reports_event_data_transfer_length() {
+        let mut m = Memory::new();
+        let mut ring = ring(&mut m);
+        let mut tracker = TransferTracker::<8>::new();
+
+        let mut e = transfer::EventData::new();
+        e.set_event_data(0xdead_beef).set_interrupt_on_completion();
+        tracker
+            .submit(
+                &mut ring,
+                &[normal(1000, false), transfer::Allowed::EventData(e)],
+            )
+            .unwrap();
+
+        let mut raw = raw_transfer_event(0xdead_beef, 600, 13);
+        raw[3] |= 1 << 2;
+        let c = tracker
+            .handle(&mut ring, &TransferEvent::try_from(raw).unwrap())
+            .unwrap();
+        assert_eq!(c.actual_length(), 600);
+        assert_eq!(c.event_data(), Some(0xdead_beef));
+    }
+
+    #[test]
+    fn td_without_ioc_is_dropped() {
+        let mut m0 = Memory::<4>::new();
+        let mut m1 = Memory::<4>::new();
+        let mut ring = ProducerRing::<transfer::Allowed, 2>::new([m0.segment(), m1.segment()]);
+        let mut tracker = TransferTracker::<8>::new();
+
+        let mut silent = transfer::Normal::new();
+        silent.set_trb_transfer_length(8);
+        tracker
+            .submit(&mut ring, &[transfer::Allowed::Normal(silent)])
+            .unwrap();
+        tracker
+            .submit(&mut ring, &[normal(512, false), normal(512, true)])
+            .unwrap();
+        let id = tracker.submit(&mut ring, &[normal(64, true)]).unwrap();
+
+        // The last TD follows the Link TRB of the first segment.
+        let c = tracker
+            .handle(&mut ring, &transfer_event(m1.base(), 0, 1))
+            .unwrap();
+        assert_eq!(c.id(), id);
+        assert_eq!(tracker.pending_trbs(), 0);
+        assert!(ring.is_empty());
+    }
+}
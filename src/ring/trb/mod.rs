@@ -176,6 +176,11 @@ macro_rules! allowed {
                 }
             }
         }
+        impl From<Allowed> for [u32; 4] {
+            fn from(a: Allowed) -> Self {
+                a.into_raw()
+            }
+        }
         $(
             impl From<$variant> for Allowed{
                 fn from(v:$variant)->Self{
@@ -76,6 +76,36 @@ impl Allowed {
         );
     }
 
+    /// Sets the value of the Interrupter Target field.
+    pub fn set_interrupter_target(&mut self, target: u16) {
+        match self {
+            Self::Normal(x) => {
+                x.set_interrupter_target(target);
+            }
+            Self::SetupStage(x) => {
+                x.set_interrupter_target(target);
+            }
+            Self::DataStage(x) => {
+                x.set_interrupter_target(target);
+            }
+            Self::StatusStage(x) => {
+                x.set_interrupter_target(target);
+            }
+            Self::Isoch(x) => {
+                x.set_interrupter_target(target);
+            }
+            Self::Link(x) => {
+                x.set_interrupter_target(target.into());
+            }
+            Self::EventData(x) => {
+                x.set_interrupter_target(target);
+            }
+            Self::Noop(x) => {
+                x.set_interrupter_target(target);
+            }
+        }
+    }
+
     /// Returns the value of the Interrupt On Completion field.
     #[must_use]
     pub fn interrupt_on_completion(&self) -> bool {
@@ -549,4 +579,15 @@ mod test {
         let pointer_read = isoch.data_buffer_pointer();
         assert_eq!(pointer, pointer_read);
     }
+
+    #[test]
+    fn interrupter_target_of_allowed() {
+        let mut trb = Allowed::Normal(Normal::new());
+        trb.set_interrupter_target(1023);
+        assert_eq!(<[u32; 4]>::from(trb)[2] >> 22, 1023);
+
+        let mut link = Allowed::Link(Link::new());
+        link.set_interrupter_target(5);
+        assert_eq!(<[u32; 4]>::from(link)[2] >> 22, 5);
+    }
 }
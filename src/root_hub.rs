@@ -0,0 +1,471 @@
+//! Root Hub Ports.
+//!
+//! The Port Register Sets do not tell which protocol each port speaks. [`RootHub`] reads the
+//! compatible port ranges of the xHCI Supported Protocol Capabilities, classifies each Root Hub
+//! Port, pairs the USB2 and USB3 ports which share a physical connector, and resets ports with the
+//! flow their protocol requires.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use core::num::NonZeroUsize;
+//! # use core::time::Duration;
+//! # use xhci::accessor::Mapper;
+//! # use xhci::controller::Clock;
+//! use xhci::accessor::array::BoundSetGeneric;
+//! use xhci::extended_capabilities::List;
+//! use xhci::root_hub::RootHub;
+//! #
+//! # const MMIO_BASE: usize = 0x1000;
+//! #
+//! # #[derive(Clone)]
+//! # struct MemoryMapper;
+//! # impl Mapper for MemoryMapper {
+//! #     unsafe fn map(&mut self, phys_base: usize, bytes: usize) -> NonZeroUsize {
+//! #         unimplemented!()
+//! #     }
+//! #
+//! #     fn unmap(&mut self, virt_base: usize, bytes: usize) {
+//! #         unimplemented!()
+//! #     }
+//! # }
+//! #
+//! # struct Timer;
+//! # impl Clock for Timer {
+//! #     fn now(&self) -> Duration {
+//! #         unimplemented!()
+//! #     }
+//! # }
+//!
+//! let mut r = unsafe { xhci::Registers::new(MMIO_BASE, MemoryMapper) };
+//! let mut l = unsafe {
+//!     List::new(MMIO_BASE, r.capability.hccparams1.read_volatile(), MemoryMapper)
+//! }
+//! .expect("The xHC does not support the xHCI Extended Capabilities.");
+//!
+//! let root_hub = RootHub::new(&mut l, Timer);
+//!
+//! for port in root_hub.ports() {
+//!     if r
+//!         .port_register_set
+//!         .set_at(port.index())
+//!         .portsc
+//!         .read_volatile()
+//!         .current_connect_status()
+//!     {
+//!         root_hub
+//!             .reset_port(&mut r, port.id())
+//!             .expect("Failed to reset the port.");
+//!     }
+//! }
+//! ```
+
+use crate::controller::Clock;
+use crate::extended_capabilities::xhci_supported_protocol::Protocol;
+use crate::extended_capabilities::List;
+use crate::registers::operational::{PortChanges, PortLinkState};
+use crate::registers::Registers;
+use crate::ring::trb::command;
+use accessor::array::BoundSetGeneric;
+use accessor::Mapper;
+use core::time::Duration;
+
+/// The maximum number of Root Hub Ports.
+const MAX_PORTS: usize = 255;
+
+/// The Root Hub Ports of the xHC.
+///
+/// See the [module-level documentation](self) for the usage.
+#[derive(Debug)]
+pub struct RootHub<C>
+where
+    C: Clock,
+{
+    ports: [Option<Port>; MAX_PORTS],
+    clock: C,
+    timeout: Duration,
+}
+impl<C> RootHub<C>
+where
+    C: Clock,
+{
+    /// Creates a Root Hub from the xHCI Supported Protocol Capabilities in `extended_capabilities`.
+    ///
+    /// The USB2 and USB3 ports are paired in the order of their Port IDs: the first USB2 port is
+    /// paired with the first USB3 port, and so on. Use [`RootHub::set_pair`] if the platform
+    /// describes the connectors differently.
+    ///
+    /// The timeout of each reset is 500 milliseconds by default.
+    pub fn new<M>(extended_capabilities: &mut List<M>, clock: C) -> Self
+    where
+        M: Mapper + Clone,
+    {
+        let mut hub = Self {
+            ports: [None; MAX_PORTS],
+            clock,
+            timeout: Duration::from_millis(500),
+        };
+
+        for p in extended_capabilities.supported_protocols() {
+            let h = p.header.read_volatile();
+            let template = Port {
+                id: 0,
+                protocol: h.protocol(),
+                major_revision: h.major_revision(),
+                minor_revision: h.minor_revision(),
+                slot_type: h.protocol_slot_type(),
+                pair: None,
+            };
+            hub.add_ports(
+                template,
+                (h.compatible_port_offset(), h.compatible_port_count()),
+            );
+        }
+
+        hub.pair_in_order();
+        hub
+    }
+
+    /// Sets the timeout of each step of [`RootHub::reset_port`].
+    pub fn set_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the information of the port `port_id`.
+    ///
+    /// This method returns [`None`] if no xHCI Supported Protocol Capability covers the port.
+    #[must_use]
+    pub fn port(&self, port_id: u8) -> Option<Port> {
+        self.ports.get(index(port_id)?).copied().flatten()
+    }
+
+    /// Returns an iterator over the ports covered by the xHCI Supported Protocol Capabilities.
+    pub fn ports(&self) -> impl Iterator<Item = Port> + '_ {
+        self.ports.iter().flatten().copied()
+    }
+
+    /// Returns the Enable Slot Command TRB for a device connected to the port `port_id`.
+    ///
+    /// The Slot Type field is set to the Protocol Slot Type of the port.
+    ///
+    /// This method returns [`None`] if no xHCI Supported Protocol Capability covers the port.
+    #[must_use]
+    pub fn enable_slot(&self, port_id: u8) -> Option<command::EnableSlot> {
+        let mut c = command::EnableSlot::new();
+        c.set_slot_type(self.port(port_id)?.slot_type());
+        Some(c)
+    }
+
+    /// Pairs the USB2 port `usb2` and the USB3 port `usb3`, which share a physical connector.
+    ///
+    /// The previous pairs of both ports are dissolved.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if `usb2` is not a USB2 port or `usb3` is not a USB3 port.
+    pub fn set_pair(&mut self, usb2: u8, usb3: u8) -> &mut Self {
+        assert!(
+            self.port(usb2).is_some_and(|p| p.is_usb2()),
+            "The port {usb2} is not a USB2 port."
+        );
+        assert!(
+            self.port(usb3).is_some_and(|p| p.is_usb3()),
+            "The port {usb3} is not a USB3 port."
+        );
+
+        for id in [usb2, usb3] {
+            if let Some(old) = self.port(id).and_then(|p| p.pair) {
+                self.port_mut(old).pair = None;
+            }
+        }
+
+        self.port_mut(usb2).pair = Some(usb3);
+        self.port_mut(usb3).pair = Some(usb2);
+        self
+    }
+
+    /// Resets the port `port_id` and waits until the port is enabled.
+    ///
+    /// A USB2 port is reset by setting the Port Reset bit. A USB3 port trains its link
+    /// automatically after a device is connected, so it is reset only if the link does not reach
+    /// the Enabled state: a Warm Port Reset is issued when the link falls into the Inactive or the
+    /// Compliance Mode state, or when the training does not complete within the timeout.
+    ///
+    /// The Port Reset Change and the Warm Port Reset Change bits are cleared. The other change bits
+    /// are preserved.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the protocol of the port is unknown, no device is connected
+    /// to the port, the port does not respond within the timeout, or the port is not enabled after
+    /// the reset.
+    pub fn reset_port<M>(&self, r: &mut Registers<M>, port_id: u8) -> Result<(), Error>
+    where
+        M: Mapper + Clone,
+    {
+        let port = self.port(port_id).ok_or(Error::UnknownProtocol(port_id))?;
+        let portsc = |r: &mut Registers<M>| {
+            r.port_register_set
+                .set_at(port.index())
+                .portsc
+                .read_volatile()
+        };
+
+        if !portsc(r).current_connect_status() {
+            return Err(Error::NotConnected);
+        }
+
+        if port.is_usb3() {
+            let trained = self.wait_until(|| {
+                let p = portsc(r);
+                p.port_enabled_disabled()
+                    || matches!(
+                        p.port_link_state(),
+                        Some(PortLinkState::Inactive | PortLinkState::ComplianceMode)
+                    )
+            });
+            if trained && portsc(r).port_enabled_disabled() {
+                return Ok(());
+            }
+
+            r.portsc_modify(port_id, |p| {
+                p.set_warm_port_reset();
+            });
+            let reset = self.wait_until(|| portsc(r).warm_port_reset_change());
+            r.acknowledge_changes(
+                port_id,
+                PortChanges::WARM_PORT_RESET | PortChanges::PORT_RESET,
+            );
+            if !reset {
+                return Err(Error::Timeout);
+            }
+        } else {
+            r.portsc_modify(port_id, |p| {
+                p.set_port_reset();
+            });
+            let reset = self.wait_until(|| {
+                let p = portsc(r);
+                p.port_reset_change() && !p.port_reset()
+            });
+            r.acknowledge_changes(port_id, PortChanges::PORT_RESET);
+            if !reset {
+                return Err(Error::Timeout);
+            }
+        }
+
+        if portsc(r).port_enabled_disabled() {
+            Ok(())
+        } else {
+            Err(Error::NotEnabled)
+        }
+    }
+
+    fn add_ports(&mut self, template: Port, ports: (u8, u8)) {
+        let (offset, count) = ports;
+        let ids = u16::from(offset)..u16::from(offset) + u16::from(count);
+
+        // A range may exceed the maximum Port ID.
+        for id in ids.filter_map(|id| u8::try_from(id).ok()) {
+            if let Some(p) = index(id).and_then(|i| self.ports.get_mut(i)) {
+                *p = Some(Port { id, ..template });
+            }
+        }
+    }
+
+    fn pair_in_order(&mut self) {
+        let ids = |f: fn(&Port) -> bool| self.ports.iter().flatten().filter(move |p| f(p));
+        let pairs = ids(Port::is_usb2).zip(ids(Port::is_usb3));
+        let mut paired = [(0, 0); MAX_PORTS];
+        let mut n = 0;
+        for (p2, p3) in pairs {
+            paired[n] = (p2.id, p3.id);
+            n += 1;
+        }
+
+        for &(usb2, usb3) in &paired[..n] {
+            self.set_pair(usb2, usb3);
+        }
+    }
+
+    fn port_mut(&mut self, port_id: u8) -> &mut Port {
+        index(port_id)
+            .and_then(|i| self.ports[i].as_mut())
+            .expect("The port does not exist.")
+    }
+
+    fn wait_until(&self, mut f: impl FnMut() -> bool) -> bool {
+        let start = self.clock.now();
+
+        while !f() {
+            if self.clock.now().saturating_sub(start) > self.timeout {
+                return false;
+            }
+
+            core::hint::spin_loop();
+        }
+
+        true
+    }
+}
+
+/// A Root Hub Port.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub struct Port {
+    id: u8,
+    protocol: Protocol,
+    major_revision: u8,
+    minor_revision: u8,
+    slot_type: u8,
+    pair: Option<u8>,
+}
+impl Port {
+    /// Returns the Port ID, which starts from 1.
+    #[must_use]
+    pub fn id(&self) -> u8 {
+        self.id
+    }
+
+    /// Returns the index of the port in [`Registers::port_register_set`].
+    #[must_use]
+    pub fn index(&self) -> usize {
+        usize::from(self.id) - 1
+    }
+
+    /// Returns the protocol the port supports.
+    #[must_use]
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    /// Returns the major revision of the protocol the port supports.
+    #[must_use]
+    pub fn major_revision(&self) -> u8 {
+        self.major_revision
+    }
+
+    /// Returns the minor revision of the protocol the port supports.
+    #[must_use]
+    pub fn minor_revision(&self) -> u8 {
+        self.minor_revision
+    }
+
+    /// Returns the Protocol Slot Type, which must be set to the Slot Type field of the Enable
+    /// Slot Command TRB for a device connected to the port.
+    #[must_use]
+    pub fn slot_type(&self) -> u8 {
+        self.slot_type
+    }
+
+    /// Returns `true` if the port is a USB2 port.
+    #[must_use]
+    pub fn is_usb2(&self) -> bool {
+        self.protocol == Protocol::Usb && self.major_revision == 2
+    }
+
+    /// Returns `true` if the port is a USB3 port.
+    #[must_use]
+    pub fn is_usb3(&self) -> bool {
+        self.protocol == Protocol::Usb && self.major_revision == 3
+    }
+
+    /// Returns the Port ID of the port of the other protocol which shares the physical connector.
+    #[must_use]
+    pub fn paired_port(&self) -> Option<u8> {
+        self.pair
+    }
+}
+
+/// An error returned by [`RootHub::reset_port`].
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub enum Error {
+    /// No xHCI Supported Protocol Capability covers the port.
+    UnknownProtocol(u8),
+    /// No device is connected to the port.
+    NotConnected,
+    /// The port did not complete the reset within the timeout.
+    Timeout,
+    /// The port is not enabled after the reset.
+    NotEnabled,
+}
+
+fn index(port_id: u8) -> Option<usize> {
+    usize::from(port_id).checked_sub(1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct Timer;
+    impl Clock for Timer {
+        fn now(&self) -> Duration {
+            Duration::ZERO
+        }
+    }
+
+    fn usb(major_revision: u8, slot_type: u8) -> Port {
+        Port {
+            id: 0,
+            protocol: Protocol::Usb,
+            major_revision,
+            minor_revision: 0,
+            slot_type,
+            pair: None,
+        }
+    }
+
+    fn root_hub() -> RootHub<Timer> {
+        let mut hub = RootHub {
+            ports: [None; MAX_PORTS],
+            clock: Timer,
+            timeout: Duration::ZERO,
+        };
+        hub.add_ports(usb(3, 1), (1, 2));
+        hub.add_ports(usb(2, 2), (3, 4));
+        hub.pair_in_order();
+        hub
+    }
+
+    #[test]
+    fn ports_are_paired_in_order() {
+        let hub = root_hub();
+
+        assert!(hub.port(1).unwrap().is_usb3());
+        assert!(hub.port(3).unwrap().is_usb2());
+        assert_eq!(hub.port(1).unwrap().paired_port(), Some(3));
+        assert_eq!(hub.port(4).unwrap().paired_port(), Some(2));
+        assert_eq!(hub.port(5).unwrap().paired_port(), None);
+        assert_eq!(hub.port(7), None);
+        assert_eq!(hub.port(0), None);
+        assert_eq!(hub.ports().count(), 6);
+    }
+
+    #[test]
+    fn enable_slot_has_slot_type_of_port() {
+        let hub = root_hub();
+
+        assert_eq!(hub.enable_slot(2).unwrap().slot_type(), 1);
+        assert_eq!(hub.enable_slot(6).unwrap().slot_type(), 2);
+        assert!(hub.enable_slot(7).is_none());
+    }
+
+    #[test]
+    fn set_pair_dissolves_previous_pairs() {
+        let mut hub = root_hub();
+        hub.set_pair(5, 1);
+
+        assert_eq!(hub.port(1).unwrap().paired_port(), Some(5));
+        assert_eq!(hub.port(5).unwrap().paired_port(), Some(1));
+        assert_eq!(hub.port(3).unwrap().paired_port(), None);
+    }
+
+    #[test]
+    fn ports_beyond_255_are_ignored() {
+        let mut hub = root_hub();
+        hub.add_ports(usb(2, 0), (255, 2));
+
+        assert!(hub.port(255).unwrap().is_usb2());
+        assert_eq!(hub.ports().count(), 7);
+    }
+}
@@ -0,0 +1,59 @@
+//! Fixtures shared by the tests of the modules.
+
+use crate::accessor::Mapper;
+use crate::ring::Segment;
+use crate::Registers;
+use core::num::NonZeroUsize;
+
+/// A mapper which maps a physical address to the same virtual address.
+#[derive(Clone, Debug)]
+pub(crate) struct Identity;
+impl Mapper for Identity {
+    unsafe fn map(&mut self, phys_base: usize, _: usize) -> NonZeroUsize {
+        NonZeroUsize::new(phys_base).unwrap()
+    }
+
+    fn unmap(&mut self, _: usize, _: usize) {}
+}
+
+/// A memory block of `L` TRBs, filled with `0xffff_ffff` so that a test notices what a ring does
+/// not write.
+#[repr(align(64))]
+#[derive(Debug)]
+pub(crate) struct Memory<const L: usize>(pub(crate) [[u32; 4]; L]);
+impl<const L: usize> Memory<L> {
+    pub(crate) fn new() -> Self {
+        Self([[0xffff_ffff; 4]; L])
+    }
+
+    pub(crate) fn addr(&mut self) -> usize {
+        self.0.as_mut_ptr() as usize
+    }
+
+    pub(crate) fn base(&self) -> u64 {
+        self.0.as_ptr() as u64
+    }
+
+    pub(crate) fn segment(&mut self) -> Segment {
+        let a = self.addr();
+        // SAFETY: The block can hold `L` TRBs, and its virtual address is the physical one.
+        unsafe { Segment::new(a, a as u64, L) }
+    }
+}
+
+/// Returns the registers in `mmio`, the MMIO space of an xHC with 8 Device Slots, 3 interrupters,
+/// and 4 ports. The Port Register Sets are at 0x420, the Doorbell Array is at 0x3000, and the
+/// Runtime Registers are at 0x4000.
+pub(crate) fn registers(mmio: &mut [u64]) -> Registers<Identity> {
+    assert_eq!(mmio.len(), 0x2000);
+
+    // CAPLENGTH and HCSPARAMS1.
+    mmio[0] = 0x20 | (((4 << 24) | (3 << 8) | 8) << 32);
+    // DBOFF.
+    mmio[2] = 0x3000 << 32;
+    // RTSOFF.
+    mmio[3] = 0x4000;
+
+    // SAFETY: `mmio` is large enough to contain all registers.
+    unsafe { Registers::new(mmio.as_mut_ptr() as usize, Identity) }
+}
@@ -0,0 +1,604 @@
+//! USB standard descriptors.
+//!
+//! Each descriptor type wraps the raw bytes of the descriptor and provides accessors to its
+//! fields. [`Descriptors`] iterates over the descriptors in a buffer, such as the data returned by
+//! a `GET_DESCRIPTOR` request for a Configuration descriptor.
+//!
+//! # Examples
+//!
+//! ```
+//! use xhci::usb::descriptor::{Descriptor, Descriptors};
+//!
+//! # let configuration = [
+//! #     9, 2, 25, 0, 1, 1, 0, 0x80, 50, // Configuration
+//! #     9, 4, 0, 0, 1, 3, 1, 1, 0, // Interface
+//! #     7, 5, 0x81, 3, 8, 0, 10, // Endpoint
+//! # ];
+//! for d in Descriptors::new(&configuration) {
+//!     match d.expect("Malformed descriptor.") {
+//!         Descriptor::Interface(i) => {
+//!             // Select the interface.
+//! #           let _ = i;
+//!         }
+//!         Descriptor::Endpoint(e) => {
+//!             // Configure the Endpoint Context of `e.dci()`.
+//! #           let _ = e;
+//!         }
+//!         _ => {}
+//!     }
+//! }
+//! ```
+
+use crate::context::EndpointType;
+use bit_field::BitField;
+use core::char::{decode_utf16, DecodeUtf16Error};
+use core::convert::{TryFrom, TryInto};
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+
+macro_rules! field {
+    ($offset:literal, $method:ident, $name:literal, u8) => {
+        #[doc = "Returns the value of the"]
+        #[doc = $name]
+        #[doc = "field."]
+        #[must_use]
+        pub fn $method(&self) -> u8 {
+            self.0[$offset]
+        }
+    };
+    ($offset:literal, $method:ident, $name:literal, u16) => {
+        #[doc = "Returns the value of the"]
+        #[doc = $name]
+        #[doc = "field."]
+        #[must_use]
+        pub fn $method(&self) -> u16 {
+            u16::from_le_bytes([self.0[$offset], self.0[$offset + 1]])
+        }
+    };
+    ($offset:literal, $method:ident, $name:literal, u32) => {
+        #[doc = "Returns the value of the"]
+        #[doc = $name]
+        #[doc = "field."]
+        #[must_use]
+        pub fn $method(&self) -> u32 {
+            u32::from_le_bytes(self.0[$offset..$offset + 4].try_into().unwrap())
+        }
+    };
+}
+
+macro_rules! add_descriptor {
+    ($name:ident, $full:literal, $ty:ident, $len:literal) => {
+        #[doc = $full]
+        #[doc = "descriptor."]
+        #[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+        pub struct $name([u8; $len]);
+        impl $name {
+            /// The length of this descriptor in bytes.
+            pub const LENGTH: usize = $len;
+
+            /// Returns the wrapped array.
+            #[must_use]
+            pub fn into_raw(self) -> [u8; $len] {
+                self.0
+            }
+        }
+        impl TryFrom<&[u8]> for $name {
+            type Error = Error;
+
+            fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+                let bytes = header(bytes, Type::$ty, $len)?;
+                Ok(Self(bytes[..$len].try_into().unwrap()))
+            }
+        }
+    };
+}
+
+add_descriptor!(Device, "Device", Device, 18);
+impl Device {
+    field!(2, usb, "bcdUSB", u16);
+    field!(4, device_class, "bDeviceClass", u8);
+    field!(5, device_subclass, "bDeviceSubClass", u8);
+    field!(6, device_protocol, "bDeviceProtocol", u8);
+    field!(7, max_packet_size0, "bMaxPacketSize0", u8);
+    field!(8, vendor_id, "idVendor", u16);
+    field!(10, product_id, "idProduct", u16);
+    field!(12, device, "bcdDevice", u16);
+    field!(14, manufacturer, "iManufacturer", u8);
+    field!(15, product, "iProduct", u8);
+    field!(16, serial_number, "iSerialNumber", u8);
+    field!(17, num_configurations, "bNumConfigurations", u8);
+
+    /// Returns the maximum packet size of the Default Control Endpoint in bytes.
+    ///
+    /// USB 3 devices encode the size as an exponent of 2 in the bMaxPacketSize0 field. This
+    /// method returns the decoded value for them, or [`None`] if the exponent does not fit in
+    /// [`u16`].
+    #[must_use]
+    pub fn max_packet_size_of_control_endpoint(&self) -> Option<u16> {
+        if self.usb() >= 0x0300 {
+            1_u16.checked_shl(self.max_packet_size0().into())
+        } else {
+            Some(self.max_packet_size0().into())
+        }
+    }
+}
+impl_debug_from_methods! {
+    Device {
+        usb,
+        device_class,
+        device_subclass,
+        device_protocol,
+        max_packet_size0,
+        vendor_id,
+        product_id,
+        device,
+        manufacturer,
+        product,
+        serial_number,
+        num_configurations,
+    }
+}
+
+add_descriptor!(Configuration, "Configuration", Configuration, 9);
+impl Configuration {
+    field!(2, total_length, "wTotalLength", u16);
+    field!(4, num_interfaces, "bNumInterfaces", u8);
+    field!(5, configuration_value, "bConfigurationValue", u8);
+    field!(6, configuration, "iConfiguration", u8);
+    field!(7, attributes, "bmAttributes", u8);
+    field!(8, max_power, "bMaxPower", u8);
+
+    /// Returns `true` if the device is self-powered in this configuration.
+    #[must_use]
+    pub fn self_powered(&self) -> bool {
+        self.attributes().get_bit(6)
+    }
+
+    /// Returns `true` if the device supports remote wakeup in this configuration.
+    #[must_use]
+    pub fn remote_wakeup(&self) -> bool {
+        self.attributes().get_bit(5)
+    }
+}
+impl_debug_from_methods! {
+    Configuration {
+        total_length,
+        num_interfaces,
+        configuration_value,
+        configuration,
+        self_powered,
+        remote_wakeup,
+        max_power,
+    }
+}
+
+add_descriptor!(Interface, "Interface", Interface, 9);
+impl Interface {
+    field!(2, interface_number, "bInterfaceNumber", u8);
+    field!(3, alternate_setting, "bAlternateSetting", u8);
+    field!(4, num_endpoints, "bNumEndpoints", u8);
+    field!(5, interface_class, "bInterfaceClass", u8);
+    field!(6, interface_subclass, "bInterfaceSubClass", u8);
+    field!(7, interface_protocol, "bInterfaceProtocol", u8);
+    field!(8, interface, "iInterface", u8);
+}
+impl_debug_from_methods! {
+    Interface {
+        interface_number,
+        alternate_setting,
+        num_endpoints,
+        interface_class,
+        interface_subclass,
+        interface_protocol,
+        interface,
+    }
+}
+
+add_descriptor!(Endpoint, "Endpoint", Endpoint, 7);
+impl Endpoint {
+    field!(2, endpoint_address, "bEndpointAddress", u8);
+    field!(3, attributes, "bmAttributes", u8);
+    field!(4, raw_max_packet_size, "wMaxPacketSize", u16);
+    field!(6, interval, "bInterval", u8);
+
+    /// Returns the endpoint number.
+    #[must_use]
+    pub fn endpoint_number(&self) -> u8 {
+        self.endpoint_address().get_bits(0..=3)
+    }
+
+    /// Returns `true` if the direction of this endpoint is IN.
+    #[must_use]
+    pub fn is_in(&self) -> bool {
+        self.endpoint_address().get_bit(7)
+    }
+
+    /// Returns the transfer type of this endpoint.
+    #[must_use]
+    pub fn transfer_type(&self) -> TransferType {
+        TransferType::from_u8(self.attributes().get_bits(0..=1)).unwrap()
+    }
+
+    /// Returns the Device Context Index of this endpoint.
+    #[must_use]
+    pub fn dci(&self) -> u8 {
+        let n = self.endpoint_number() * 2;
+
+        if self.is_in() || self.transfer_type() == TransferType::Control {
+            n + 1
+        } else {
+            n
+        }
+    }
+
+    /// Returns the value for the Endpoint Type field of the Endpoint Context.
+    #[must_use]
+    pub fn endpoint_type(&self) -> EndpointType {
+        match (self.transfer_type(), self.is_in()) {
+            (TransferType::Control, _) => EndpointType::Control,
+            (TransferType::Isochronous, false) => EndpointType::IsochOut,
+            (TransferType::Isochronous, true) => EndpointType::IsochIn,
+            (TransferType::Bulk, false) => EndpointType::BulkOut,
+            (TransferType::Bulk, true) => EndpointType::BulkIn,
+            (TransferType::Interrupt, false) => EndpointType::InterruptOut,
+            (TransferType::Interrupt, true) => EndpointType::InterruptIn,
+        }
+    }
+
+    /// Returns the maximum packet size in bytes.
+    ///
+    /// This is the value for the Max Packet Size field of the Endpoint Context.
+    #[must_use]
+    pub fn max_packet_size(&self) -> u16 {
+        self.raw_max_packet_size().get_bits(0..=10)
+    }
+
+    /// Returns the number of additional transactions per microframe of a high-speed periodic
+    /// endpoint.
+    #[must_use]
+    pub fn additional_transactions(&self) -> u8 {
+        self.raw_max_packet_size()
+            .get_bits(11..=12)
+            .try_into()
+            .unwrap()
+    }
+}
+impl_debug_from_methods! {
+    Endpoint {
+        endpoint_address,
+        transfer_type,
+        max_packet_size,
+        additional_transactions,
+        interval,
+    }
+}
+
+add_descriptor!(
+    SuperSpeedEndpointCompanion,
+    "SuperSpeed Endpoint Companion",
+    SuperSpeedEndpointCompanion,
+    6
+);
+impl SuperSpeedEndpointCompanion {
+    field!(2, max_burst, "bMaxBurst", u8);
+    field!(3, attributes, "bmAttributes", u8);
+    field!(4, bytes_per_interval, "wBytesPerInterval", u16);
+
+    /// Returns the value of the `MaxStreams` field of a bulk endpoint.
+    #[must_use]
+    pub fn max_streams(&self) -> u8 {
+        self.attributes().get_bits(0..=4)
+    }
+
+    /// Returns the value of the Mult field of an isochronous endpoint.
+    #[must_use]
+    pub fn mult(&self) -> u8 {
+        self.attributes().get_bits(0..=1)
+    }
+
+    /// Returns `true` if a SuperSpeedPlus Isochronous Endpoint Companion descriptor follows this
+    /// descriptor.
+    #[must_use]
+    pub fn ssp_isochronous_companion(&self) -> bool {
+        self.attributes().get_bit(7)
+    }
+}
+impl_debug_from_methods! {
+    SuperSpeedEndpointCompanion {
+        max_burst,
+        attributes,
+        bytes_per_interval,
+    }
+}
+
+add_descriptor!(
+    SuperSpeedPlusIsochronousEndpointCompanion,
+    "SuperSpeedPlus Isochronous Endpoint Companion",
+    SuperSpeedPlusIsochronousEndpointCompanion,
+    8
+);
+impl SuperSpeedPlusIsochronousEndpointCompanion {
+    field!(4, bytes_per_interval, "dwBytesPerInterval", u32);
+}
+impl_debug_from_methods! {
+    SuperSpeedPlusIsochronousEndpointCompanion {
+        bytes_per_interval,
+    }
+}
+
+add_descriptor!(Bos, "Binary device Object Store (BOS)", Bos, 5);
+impl Bos {
+    field!(2, total_length, "wTotalLength", u16);
+    field!(4, num_device_caps, "bNumDeviceCaps", u8);
+}
+impl_debug_from_methods! {
+    Bos {
+        total_length,
+        num_device_caps,
+    }
+}
+
+/// String descriptor.
+///
+/// The String descriptor of index 0 contains the LANGIDs the device supports instead of a string.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub struct StringDescriptor<'a>(&'a [u8]);
+impl<'a> StringDescriptor<'a> {
+    /// Returns the UTF-16 code units of the string, or the LANGIDs.
+    pub fn code_units(&self) -> impl Iterator<Item = u16> + 'a {
+        self.0[2..]
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+    }
+
+    /// Returns the characters of the string.
+    pub fn chars(&self) -> impl Iterator<Item = Result<char, DecodeUtf16Error>> + 'a {
+        decode_utf16(self.code_units())
+    }
+}
+impl<'a> TryFrom<&'a [u8]> for StringDescriptor<'a> {
+    type Error = Error;
+
+    fn try_from(bytes: &'a [u8]) -> Result<Self, Self::Error> {
+        let bytes = header(bytes, Type::String, 2)?;
+        Ok(Self(bytes))
+    }
+}
+
+/// A descriptor of any type.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash, Debug)]
+pub enum Descriptor<'a> {
+    /// Device descriptor.
+    Device(Device),
+    /// Configuration descriptor.
+    Configuration(Configuration),
+    /// String descriptor.
+    String(StringDescriptor<'a>),
+    /// Interface descriptor.
+    Interface(Interface),
+    /// Endpoint descriptor.
+    Endpoint(Endpoint),
+    /// BOS descriptor.
+    Bos(Bos),
+    /// SuperSpeed Endpoint Companion descriptor.
+    SuperSpeedEndpointCompanion(SuperSpeedEndpointCompanion),
+    /// SuperSpeedPlus Isochronous Endpoint Companion descriptor.
+    SuperSpeedPlusIsochronousEndpointCompanion(SuperSpeedPlusIsochronousEndpointCompanion),
+    /// A descriptor of other types, such as class-specific descriptors. The slice contains the
+    /// whole descriptor including the bLength and the bDescriptorType fields.
+    Other(&'a [u8]),
+}
+impl<'a> TryFrom<&'a [u8]> for Descriptor<'a> {
+    type Error = Error;
+
+    fn try_from(bytes: &'a [u8]) -> Result<Self, Self::Error> {
+        let ty = bytes.get(1).ok_or(Error::Truncated)?;
+
+        Ok(match Type::from_u8(*ty) {
+            Some(Type::Device) => Self::Device(bytes.try_into()?),
+            Some(Type::Configuration) => Self::Configuration(bytes.try_into()?),
+            Some(Type::String) => Self::String(bytes.try_into()?),
+            Some(Type::Interface) => Self::Interface(bytes.try_into()?),
+            Some(Type::Endpoint) => Self::Endpoint(bytes.try_into()?),
+            Some(Type::Bos) => Self::Bos(bytes.try_into()?),
+            Some(Type::SuperSpeedEndpointCompanion) => {
+                Self::SuperSpeedEndpointCompanion(bytes.try_into()?)
+            }
+            Some(Type::SuperSpeedPlusIsochronousEndpointCompanion) => {
+                Self::SuperSpeedPlusIsochronousEndpointCompanion(bytes.try_into()?)
+            }
+            None => Self::Other(&bytes[..usize::from(length(bytes)?)]),
+        })
+    }
+}
+
+/// An iterator over the descriptors in a buffer.
+///
+/// The iterator stops after returning an error.
+#[derive(Clone, Debug)]
+pub struct Descriptors<'a>(&'a [u8]);
+impl<'a> Descriptors<'a> {
+    /// Creates a new iterator over the descriptors in `bytes`.
+    #[must_use]
+    pub fn new(bytes: &'a [u8]) -> Self {
+        Self(bytes)
+    }
+}
+impl<'a> Iterator for Descriptors<'a> {
+    type Item = Result<Descriptor<'a>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0.is_empty() {
+            return None;
+        }
+
+        let r = length(self.0).and_then(|l| {
+            let (d, rest) = self.0.split_at(l.into());
+            self.0 = rest;
+            Descriptor::try_from(d)
+        });
+        if r.is_err() {
+            self.0 = &[];
+        }
+
+        Some(r)
+    }
+}
+
+/// The transfer type of an endpoint.
+#[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Hash, FromPrimitive)]
+pub enum TransferType {
+    /// Control.
+    Control = 0,
+    /// Isochronous.
+    Isochronous = 1,
+    /// Bulk.
+    Bulk = 2,
+    /// Interrupt.
+    Interrupt = 3,
+}
+
+/// Descriptor Type.
+#[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Hash, FromPrimitive)]
+pub enum Type {
+    /// Device.
+    Device = 1,
+    /// Configuration.
+    Configuration = 2,
+    /// String.
+    String = 3,
+    /// Interface.
+    Interface = 4,
+    /// Endpoint.
+    Endpoint = 5,
+    /// BOS.
+    Bos = 15,
+    /// SuperSpeed Endpoint Companion.
+    SuperSpeedEndpointCompanion = 48,
+    /// SuperSpeedPlus Isochronous Endpoint Companion.
+    SuperSpeedPlusIsochronousEndpointCompanion = 49,
+}
+
+/// An error returned when parsing a descriptor fails.
+#[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum Error {
+    /// The buffer is shorter than the bLength field.
+    Truncated,
+    /// The bLength field is too small for the descriptor type.
+    InvalidLength(u8),
+    /// The bDescriptorType field is not the expected one.
+    UnexpectedType(u8),
+}
+
+/// Returns the value of the bLength field after checking that `bytes` contains the whole
+/// descriptor.
+fn length(bytes: &[u8]) -> Result<u8, Error> {
+    let l = *bytes.first().ok_or(Error::Truncated)?;
+
+    if l < 2 {
+        Err(Error::InvalidLength(l))
+    } else if bytes.len() < l.into() {
+        Err(Error::Truncated)
+    } else {
+        Ok(l)
+    }
+}
+
+/// Checks the header of a descriptor, and returns the descriptor without the trailing bytes.
+fn header(bytes: &[u8], ty: Type, min_len: u8) -> Result<&[u8], Error> {
+    let l = length(bytes)?;
+
+    if bytes[1] != ty as u8 {
+        Err(Error::UnexpectedType(bytes[1]))
+    } else if l < min_len {
+        Err(Error::InvalidLength(l))
+    } else {
+        Ok(&bytes[..l.into()])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[rustfmt::skip]
+    const CONFIGURATION: [u8; 44] = [
+        9, 2, 44, 0, 1, 1, 0, 0xc0, 50,
+        9, 4, 0, 0, 2, 8, 6, 0x50, 0,
+        7, 5, 0x81, 2, 0, 4, 0,
+        6, 48, 15, 0, 0, 0,
+        7, 5, 0x02, 2, 0, 4, 0,
+        6, 48, 15, 4, 0, 0,
+    ];
+
+    #[test]
+    fn configuration_blob_is_parsed() {
+        let d = Descriptors::new(&CONFIGURATION)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(d.len(), 6);
+
+        let Descriptor::Configuration(c) = d[0] else {
+            panic!()
+        };
+        assert_eq!(c.total_length(), 44);
+        assert!(c.self_powered());
+
+        let Descriptor::Interface(i) = d[1] else {
+            panic!()
+        };
+        assert_eq!(i.interface_class(), 8);
+
+        let Descriptor::Endpoint(e) = d[2] else {
+            panic!()
+        };
+        assert_eq!(e.dci(), 3);
+        assert_eq!(e.endpoint_type(), EndpointType::BulkIn);
+        assert_eq!(e.max_packet_size(), 1024);
+
+        let Descriptor::Endpoint(e) = d[4] else {
+            panic!()
+        };
+        assert_eq!(e.dci(), 4);
+
+        let Descriptor::SuperSpeedEndpointCompanion(c) = d[5] else {
+            panic!()
+        };
+        assert_eq!(c.max_burst(), 15);
+        assert_eq!(c.max_streams(), 4);
+    }
+
+    #[test]
+    fn control_endpoint_size_is_decoded() {
+        let mut d = [18, 1, 0x00, 0x02, 0, 0, 0, 64, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+        let device = Device::try_from(&d[..]).unwrap();
+        assert_eq!(device.max_packet_size_of_control_endpoint(), Some(64));
+
+        d[2..4].copy_from_slice(&[0x20, 0x03]);
+        d[7] = 9;
+        let device = Device::try_from(&d[..]).unwrap();
+        assert_eq!(device.max_packet_size_of_control_endpoint(), Some(512));
+
+        d[7] = 16;
+        let device = Device::try_from(&d[..]).unwrap();
+        assert_eq!(device.max_packet_size_of_control_endpoint(), None);
+    }
+
+    #[test]
+    fn truncated_descriptor_stops_iteration() {
+        let mut d = Descriptors::new(&CONFIGURATION[..12]);
+        assert!(d.next().unwrap().is_ok());
+        assert_eq!(d.next(), Some(Err(Error::Truncated)));
+        assert_eq!(d.next(), None);
+    }
+
+    #[test]
+    fn string_is_decoded() {
+        let s = [8, 3, b'x', 0, b'H', 0, b'C', 0];
+        let Ok(Descriptor::String(s)) = Descriptor::try_from(&s[..]) else {
+            panic!()
+        };
+        assert!(s.chars().map(Result::unwrap).eq("xHC".chars()));
+    }
+}
@@ -0,0 +1,73 @@
+//! USB definitions which are not specific to xHCI.
+//!
+//! The types of this module are used to build the xHCI structures from the information the USB
+//! devices provide.
+
+pub mod descriptor;
+pub mod request;
+
+/// The speed of a USB device.
+#[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum Speed {
+    /// Low-speed (1.5 Mb/s).
+    Low,
+    /// Full-speed (12 Mb/s).
+    Full,
+    /// High-speed (480 Mb/s).
+    High,
+    /// SuperSpeed (5 Gb/s).
+    SuperSpeed,
+    /// SuperSpeedPlus (10 Gb/s or faster).
+    SuperSpeedPlus,
+}
+impl Speed {
+    /// Converts the Port Speed field of the PORTSC register into a speed.
+    ///
+    /// This method assumes the default Protocol Speed ID mapping which the xHC uses if the xHCI
+    /// Supported Protocol Capability does not define any Protocol Speed ID. It returns [`None`]
+    /// if `port_speed` is not one of the default IDs.
+    #[must_use]
+    pub fn from_port_speed(port_speed: u8) -> Option<Self> {
+        match port_speed {
+            1 => Some(Self::Full),
+            2 => Some(Self::Low),
+            3 => Some(Self::High),
+            4 => Some(Self::SuperSpeed),
+            5 => Some(Self::SuperSpeedPlus),
+            _ => None,
+        }
+    }
+
+    /// Returns the default Protocol Speed ID of the speed, which is the value for the Speed field
+    /// of the Slot Context.
+    #[must_use]
+    pub fn default_port_speed(self) -> u8 {
+        match self {
+            Self::Full => 1,
+            Self::Low => 2,
+            Self::High => 3,
+            Self::SuperSpeed => 4,
+            Self::SuperSpeedPlus => 5,
+        }
+    }
+
+    /// Returns `true` if the speed is SuperSpeed or faster.
+    #[must_use]
+    pub fn is_super_speed_or_faster(self) -> bool {
+        self >= Self::SuperSpeed
+    }
+
+    /// Returns the maximum packet size of the Default Control Endpoint before the Device
+    /// descriptor is read.
+    ///
+    /// For full-speed devices, this method returns 8, which is the smallest allowed value. Read
+    /// the first 8 bytes of the Device descriptor to know the actual size.
+    #[must_use]
+    pub fn default_max_packet_size(self) -> u16 {
+        match self {
+            Self::Low | Self::Full => 8,
+            Self::High => 64,
+            Self::SuperSpeed | Self::SuperSpeedPlus => 512,
+        }
+    }
+}
@@ -0,0 +1,549 @@
+//! Control requests.
+//!
+//! [`ControlRequest`] holds the eight bytes of a SETUP packet. The constructors of it cover the
+//! standard requests, the hub class requests, and some of the other class requests.
+//! [`ControlRequest::transfer_trbs`] converts a request into the Setup, Data, and Status Stage
+//! TRBs of a control transfer.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use xhci::ring::{producer::ProducerRing, trb::transfer::Allowed};
+//! use xhci::usb::{descriptor, request::ControlRequest};
+//!
+//! # let mut ring: ProducerRing<Allowed, 1> = unimplemented!();
+//! # let buffer_phys = 0x1000;
+//! let request = ControlRequest::get_descriptor(descriptor::Type::Device, 0, 18);
+//!
+//! for trb in request.transfer_trbs(buffer_phys) {
+//!     ring.enqueue(trb).expect("The Transfer Ring is full.");
+//! }
+//! ```
+
+use super::descriptor;
+use crate::ring::trb::transfer::{self, Allowed, DataStage, Direction, SetupStage, StatusStage};
+use bit_field::BitField;
+use core::array;
+use core::iter::Flatten;
+
+const GET_STATUS: u8 = 0;
+const CLEAR_FEATURE: u8 = 1;
+const SET_FEATURE: u8 = 3;
+const SET_ADDRESS: u8 = 5;
+const GET_DESCRIPTOR: u8 = 6;
+const SET_DESCRIPTOR: u8 = 7;
+const GET_CONFIGURATION: u8 = 8;
+const SET_CONFIGURATION: u8 = 9;
+const GET_INTERFACE: u8 = 10;
+const SET_INTERFACE: u8 = 11;
+const SYNCH_FRAME: u8 = 12;
+const SET_SEL: u8 = 48;
+const SET_ISOCHRONOUS_DELAY: u8 = 49;
+
+const CLEAR_TT_BUFFER: u8 = 8;
+const RESET_TT: u8 = 9;
+const SET_HUB_DEPTH: u8 = 12;
+
+const HID_SET_IDLE: u8 = 0x0a;
+const HID_SET_PROTOCOL: u8 = 0x0b;
+
+const MASS_STORAGE_GET_MAX_LUN: u8 = 0xfe;
+const MASS_STORAGE_RESET: u8 = 0xff;
+
+/// A control request, namely the data of a SETUP packet.
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub struct ControlRequest {
+    request_type: u8,
+    request: u8,
+    value: u16,
+    index: u16,
+    length: u16,
+}
+impl ControlRequest {
+    /// Creates a new request whose wValue, wIndex, and wLength fields are 0.
+    ///
+    /// The Recipient determines the wIndex field. Use [`ControlRequest::set_index`] after this
+    /// method if the request needs another value.
+    #[must_use]
+    pub fn new(ty: RequestType, recipient: Recipient, request: u8) -> Self {
+        let mut request_type = 0;
+        request_type.set_bits(0..=4, recipient.value());
+        request_type.set_bits(5..=6, ty as u8);
+
+        Self {
+            request_type,
+            request,
+            value: 0,
+            index: recipient.index(),
+            length: 0,
+        }
+    }
+
+    /// Creates a `GET_STATUS` request.
+    #[must_use]
+    pub fn get_status(recipient: Recipient) -> Self {
+        *Self::standard(recipient, GET_STATUS).set_length(2).set_in()
+    }
+
+    /// Creates a `CLEAR_FEATURE` request.
+    #[must_use]
+    pub fn clear_feature(recipient: Recipient, feature: u16) -> Self {
+        *Self::standard(recipient, CLEAR_FEATURE).set_value(feature)
+    }
+
+    /// Creates a `SET_FEATURE` request.
+    #[must_use]
+    pub fn set_feature(recipient: Recipient, feature: u16) -> Self {
+        *Self::standard(recipient, SET_FEATURE).set_value(feature)
+    }
+
+    /// Creates a `SET_ADDRESS` request.
+    ///
+    /// Note that xHCI software must not send this request. Issue an Address Device Command
+    /// instead.
+    #[must_use]
+    pub fn set_address(address: u8) -> Self {
+        *Self::standard(Recipient::Device, SET_ADDRESS).set_value(address.into())
+    }
+
+    /// Creates a `GET_DESCRIPTOR` request which reads `length` bytes of the `index`th descriptor of
+    /// the type `ty`.
+    #[must_use]
+    pub fn get_descriptor(ty: descriptor::Type, index: u8, length: u16) -> Self {
+        *Self::standard(Recipient::Device, GET_DESCRIPTOR)
+            .set_value(descriptor_value(ty as u8, index))
+            .set_length(length)
+            .set_in()
+    }
+
+    /// Creates a `GET_DESCRIPTOR` request which reads `length` bytes of the `index`th String
+    /// descriptor in the language `language_id`.
+    #[must_use]
+    pub fn get_string_descriptor(index: u8, language_id: u16, length: u16) -> Self {
+        *Self::get_descriptor(descriptor::Type::String, index, length).set_index(language_id)
+    }
+
+    /// Creates a `SET_DESCRIPTOR` request which writes `length` bytes of the `index`th descriptor of
+    /// the type `ty`.
+    #[must_use]
+    pub fn set_descriptor(ty: descriptor::Type, index: u8, length: u16) -> Self {
+        *Self::standard(Recipient::Device, SET_DESCRIPTOR)
+            .set_value(descriptor_value(ty as u8, index))
+            .set_length(length)
+    }
+
+    /// Creates a `GET_CONFIGURATION` request.
+    #[must_use]
+    pub fn get_configuration() -> Self {
+        *Self::standard(Recipient::Device, GET_CONFIGURATION)
+            .set_length(1)
+            .set_in()
+    }
+
+    /// Creates a `SET_CONFIGURATION` request.
+    ///
+    /// Issue a Configure Endpoint Command before sending this request.
+    #[must_use]
+    pub fn set_configuration(configuration_value: u8) -> Self {
+        *Self::standard(Recipient::Device, SET_CONFIGURATION).set_value(configuration_value.into())
+    }
+
+    /// Creates a `GET_INTERFACE` request.
+    #[must_use]
+    pub fn get_interface(interface: u8) -> Self {
+        *Self::standard(Recipient::Interface(interface), GET_INTERFACE)
+            .set_length(1)
+            .set_in()
+    }
+
+    /// Creates a `SET_INTERFACE` request.
+    #[must_use]
+    pub fn set_interface(interface: u8, alternate_setting: u8) -> Self {
+        *Self::standard(Recipient::Interface(interface), SET_INTERFACE)
+            .set_value(alternate_setting.into())
+    }
+
+    /// Creates a `SYNCH_FRAME` request.
+    #[must_use]
+    pub fn synch_frame(endpoint_address: u8) -> Self {
+        *Self::standard(Recipient::Endpoint(endpoint_address), SYNCH_FRAME)
+            .set_length(2)
+            .set_in()
+    }
+
+    /// Creates a `SET_SEL` request. The data stage carries the six bytes of the exit latencies.
+    #[must_use]
+    pub fn set_sel() -> Self {
+        *Self::standard(Recipient::Device, SET_SEL).set_length(6)
+    }
+
+    /// Creates a `SET_ISOCH_DELAY` request.
+    #[must_use]
+    pub fn set_isochronous_delay(delay_ns: u16) -> Self {
+        *Self::standard(Recipient::Device, SET_ISOCHRONOUS_DELAY).set_value(delay_ns)
+    }
+
+    /// Creates a `GetHubDescriptor` request.
+    ///
+    /// `ty` is 0x29 for the USB 2.0 hub descriptor and 0x2a for the SuperSpeed hub descriptor.
+    #[must_use]
+    pub fn get_hub_descriptor(ty: u8, length: u16) -> Self {
+        *Self::class(Recipient::Device, GET_DESCRIPTOR)
+            .set_value(descriptor_value(ty, 0))
+            .set_length(length)
+            .set_in()
+    }
+
+    /// Creates a `GetHubStatus` request.
+    #[must_use]
+    pub fn get_hub_status() -> Self {
+        *Self::class(Recipient::Device, GET_STATUS)
+            .set_length(4)
+            .set_in()
+    }
+
+    /// Creates a `GetPortStatus` request.
+    #[must_use]
+    pub fn get_port_status(port: u8) -> Self {
+        *Self::class(Recipient::Other(port.into()), GET_STATUS)
+            .set_length(4)
+            .set_in()
+    }
+
+    /// Creates a `SetHubFeature` request.
+    #[must_use]
+    pub fn set_hub_feature(feature: u16) -> Self {
+        *Self::class(Recipient::Device, SET_FEATURE).set_value(feature)
+    }
+
+    /// Creates a `ClearHubFeature` request.
+    #[must_use]
+    pub fn clear_hub_feature(feature: u16) -> Self {
+        *Self::class(Recipient::Device, CLEAR_FEATURE).set_value(feature)
+    }
+
+    /// Creates a `SetPortFeature` request.
+    #[must_use]
+    pub fn set_port_feature(port: u8, feature: u16) -> Self {
+        *Self::class(Recipient::Other(port.into()), SET_FEATURE).set_value(feature)
+    }
+
+    /// Creates a `ClearPortFeature` request.
+    #[must_use]
+    pub fn clear_port_feature(port: u8, feature: u16) -> Self {
+        *Self::class(Recipient::Other(port.into()), CLEAR_FEATURE).set_value(feature)
+    }
+
+    /// Creates a `SetHubDepth` request of SuperSpeed hubs.
+    #[must_use]
+    pub fn set_hub_depth(depth: u16) -> Self {
+        *Self::class(Recipient::Device, SET_HUB_DEPTH).set_value(depth)
+    }
+
+    /// Creates a `ClearTTBuffer` request.
+    ///
+    /// `value` is the wValue field which contains the device address, the endpoint number, the
+    /// endpoint type, and the direction of the endpoint whose transaction was interrupted.
+    #[must_use]
+    pub fn clear_tt_buffer(value: u16, tt_port: u8) -> Self {
+        *Self::class(Recipient::Other(tt_port.into()), CLEAR_TT_BUFFER).set_value(value)
+    }
+
+    /// Creates a `ResetTT` request.
+    #[must_use]
+    pub fn reset_tt(tt_port: u8) -> Self {
+        Self::class(Recipient::Other(tt_port.into()), RESET_TT)
+    }
+
+    /// Creates a HID class `SET_IDLE` request.
+    #[must_use]
+    pub fn hid_set_idle(interface: u8, duration: u8, report_id: u8) -> Self {
+        *Self::class(Recipient::Interface(interface), HID_SET_IDLE)
+            .set_value(u16::from_le_bytes([report_id, duration]))
+    }
+
+    /// Creates a HID class `SET_PROTOCOL` request.
+    ///
+    /// `protocol` is 0 for the boot protocol and 1 for the report protocol.
+    #[must_use]
+    pub fn hid_set_protocol(interface: u8, protocol: u16) -> Self {
+        *Self::class(Recipient::Interface(interface), HID_SET_PROTOCOL).set_value(protocol)
+    }
+
+    /// Creates a Bulk-Only Mass Storage Reset request.
+    #[must_use]
+    pub fn mass_storage_reset(interface: u8) -> Self {
+        Self::class(Recipient::Interface(interface), MASS_STORAGE_RESET)
+    }
+
+    /// Creates a Get Max LUN request of the Bulk-Only Mass Storage class.
+    #[must_use]
+    pub fn mass_storage_get_max_lun(interface: u8) -> Self {
+        *Self::class(Recipient::Interface(interface), MASS_STORAGE_GET_MAX_LUN)
+            .set_length(1)
+            .set_in()
+    }
+
+    /// Sets the direction bit of the bmRequestType field.
+    pub fn set_direction(&mut self, d: Direction) -> &mut Self {
+        self.request_type.set_bit(7, d.into());
+        self
+    }
+
+    /// Sets the value of the wValue field.
+    pub fn set_value(&mut self, value: u16) -> &mut Self {
+        self.value = value;
+        self
+    }
+
+    /// Sets the value of the wIndex field.
+    pub fn set_index(&mut self, index: u16) -> &mut Self {
+        self.index = index;
+        self
+    }
+
+    /// Sets the value of the wLength field.
+    pub fn set_length(&mut self, length: u16) -> &mut Self {
+        self.length = length;
+        self
+    }
+
+    /// Returns the value of the bmRequestType field.
+    #[must_use]
+    pub fn request_type(&self) -> u8 {
+        self.request_type
+    }
+
+    /// Returns the value of the bRequest field.
+    #[must_use]
+    pub fn request(&self) -> u8 {
+        self.request
+    }
+
+    /// Returns the value of the wValue field.
+    #[must_use]
+    pub fn value(&self) -> u16 {
+        self.value
+    }
+
+    /// Returns the value of the wIndex field.
+    #[must_use]
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    /// Returns the value of the wLength field.
+    #[must_use]
+    pub fn length(&self) -> u16 {
+        self.length
+    }
+
+    /// Returns the direction of the data stage.
+    #[must_use]
+    pub fn direction(&self) -> Direction {
+        self.request_type.get_bit(7).into()
+    }
+
+    /// Returns the Setup, Data, and Status Stage TRBs of this request.
+    ///
+    /// The Data Stage TRB is created only if the wLength field is not 0, and it transfers the data
+    /// from or to `data_buffer`, which is the physical address of a buffer of at least wLength
+    /// bytes. `data_buffer` is ignored if wLength is 0.
+    ///
+    /// The TRBs are not chained, as each stage is a single TRB. The Interrupt On Completion bit of
+    /// the Status Stage TRB is set so that the xHC reports the completion of the transfer.
+    #[must_use]
+    pub fn transfer_trbs(&self, data_buffer: u64) -> ControlTransfer {
+        let mut setup = SetupStage::new();
+        setup
+            .set_request_type(self.request_type)
+            .set_request(self.request)
+            .set_value(self.value)
+            .set_index(self.index)
+            .set_length(self.length);
+
+        let mut status = StatusStage::new();
+        status.set_interrupt_on_completion();
+
+        let data = if self.length == 0 {
+            setup.set_transfer_type(transfer::TransferType::No);
+            status.set_direction();
+
+            None
+        } else {
+            let mut data = DataStage::new();
+            data.set_data_buffer_pointer(data_buffer)
+                .set_trb_transfer_length(self.length.into())
+                .set_direction(self.direction());
+
+            match self.direction() {
+                Direction::In => {
+                    setup.set_transfer_type(transfer::TransferType::In);
+                }
+                Direction::Out => {
+                    setup.set_transfer_type(transfer::TransferType::Out);
+                    status.set_direction();
+                }
+            }
+
+            Some(data)
+        };
+
+        ControlTransfer {
+            setup,
+            data,
+            status,
+        }
+    }
+
+    fn standard(recipient: Recipient, request: u8) -> Self {
+        Self::new(RequestType::Standard, recipient, request)
+    }
+
+    fn class(recipient: Recipient, request: u8) -> Self {
+        Self::new(RequestType::Class, recipient, request)
+    }
+
+    fn set_in(&mut self) -> &mut Self {
+        self.set_direction(Direction::In)
+    }
+}
+impl_debug_from_methods! {
+    ControlRequest {
+        request_type,
+        request,
+        value,
+        index,
+        length,
+    }
+}
+
+/// The TRBs of a control transfer.
+///
+/// Iterating over this struct yields the TRBs in the order to enqueue them.
+#[derive(Copy, Clone, Debug)]
+pub struct ControlTransfer {
+    setup: SetupStage,
+    data: Option<DataStage>,
+    status: StatusStage,
+}
+impl ControlTransfer {
+    /// Returns the Setup Stage TRB.
+    #[must_use]
+    pub fn setup(&self) -> SetupStage {
+        self.setup
+    }
+
+    /// Returns the Data Stage TRB, if the transfer has a data stage.
+    #[must_use]
+    pub fn data(&self) -> Option<DataStage> {
+        self.data
+    }
+
+    /// Returns the Status Stage TRB.
+    #[must_use]
+    pub fn status(&self) -> StatusStage {
+        self.status
+    }
+}
+impl IntoIterator for ControlTransfer {
+    type Item = Allowed;
+    type IntoIter = Flatten<array::IntoIter<Option<Allowed>, 3>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        [
+            Some(Allowed::SetupStage(self.setup)),
+            self.data.map(Allowed::DataStage),
+            Some(Allowed::StatusStage(self.status)),
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+/// The type of a request.
+#[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum RequestType {
+    /// Standard.
+    Standard = 0,
+    /// Class.
+    Class = 1,
+    /// Vendor.
+    Vendor = 2,
+}
+
+/// The recipient of a request.
+///
+/// The values of the variants are the ones of the wIndex field.
+#[derive(Copy, Clone, Debug, Ord, PartialOrd, Eq, PartialEq, Hash)]
+pub enum Recipient {
+    /// The device.
+    Device,
+    /// The interface of the number.
+    Interface(u8),
+    /// The endpoint of the address, including the direction bit.
+    Endpoint(u8),
+    /// Other recipient, such as a port of a hub.
+    Other(u16),
+}
+impl Recipient {
+    fn value(self) -> u8 {
+        match self {
+            Self::Device => 0,
+            Self::Interface(_) => 1,
+            Self::Endpoint(_) => 2,
+            Self::Other(_) => 3,
+        }
+    }
+
+    fn index(self) -> u16 {
+        match self {
+            Self::Device => 0,
+            Self::Interface(i) | Self::Endpoint(i) => i.into(),
+            Self::Other(i) => i,
+        }
+    }
+}
+
+fn descriptor_value(ty: u8, index: u8) -> u16 {
+    u16::from_le_bytes([index, ty])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn in_request_has_in_data_stage_and_out_status_stage() {
+        let r = ControlRequest::get_descriptor(descriptor::Type::Configuration, 0, 9);
+        assert_eq!(r.request_type(), 0x80);
+        assert_eq!(r.value(), 0x0200);
+
+        let t = r.transfer_trbs(0x1000);
+        assert_eq!(t.setup().transfer_type(), transfer::TransferType::In);
+        assert_eq!(t.setup().length(), 9);
+
+        let d = t.data().unwrap();
+        assert_eq!(d.direction(), Direction::In);
+        assert_eq!(d.data_buffer_pointer(), 0x1000);
+        assert_eq!(d.trb_transfer_length(), 9);
+        assert!(!d.chain_bit());
+
+        assert!(!t.status().direction());
+        assert!(t.status().interrupt_on_completion());
+        assert_eq!(t.into_iter().count(), 3);
+    }
+
+    #[test]
+    fn no_data_request_has_in_status_stage() {
+        let r = ControlRequest::set_port_feature(3, 4);
+        assert_eq!(r.request_type(), 0x23);
+        assert_eq!(r.index(), 3);
+
+        let t = r.transfer_trbs(0);
+        assert_eq!(t.setup().transfer_type(), transfer::TransferType::No);
+        assert!(t.data().is_none());
+        assert!(t.status().direction());
+        assert_eq!(t.into_iter().count(), 2);
+    }
+}